@@ -0,0 +1,273 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Amounts of tokens given to the client API.
+//!
+//! JS numbers can't hold every amount, so amounts are given either as decimal
+//! strings of tokens, such as `"0.125"`, or as bigints of attos, the smallest unit
+//! (10^-18 tokens). Whole numbers of tokens are still accepted as plain numbers.
+//!
+//! Outside of JS, integers of at most 64 bits, which is what JSON numbers are read
+//! as, are numbers of tokens, and wider ones play the role of bigints and are attos.
+
+use std::fmt;
+
+use linera_base::data_types::Amount;
+use serde::{Deserialize, Deserializer};
+
+/// The largest integer JS numbers hold exactly.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// An amount of tokens given by JS: a decimal string of tokens, a bigint of attos,
+/// or a whole number of tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenAmount(pub Amount);
+
+impl From<TokenAmount> for Amount {
+    fn from(amount: TokenAmount) -> Self {
+        amount.0
+    }
+}
+
+impl From<Amount> for TokenAmount {
+    fn from(amount: Amount) -> Self {
+        Self(amount)
+    }
+}
+
+impl TokenAmount {
+    /// Parses a decimal string of tokens, with at most 18 decimals.
+    fn parse_tokens(tokens: &str) -> Result<Self, String> {
+        tokens
+            .trim()
+            .parse()
+            .map(Self)
+            .map_err(|e| format!("Invalid amount {tokens:?}: {e}"))
+    }
+
+    /// Converts a number of tokens, which must be a whole non-negative number.
+    fn from_number(tokens: f64) -> Result<Self, String> {
+        if !(0.0..=MAX_SAFE_INTEGER).contains(&tokens) || tokens.fract() > 0.0 {
+            return Err(format!(
+                "Invalid amount {tokens}: amounts given as numbers must be whole numbers of \
+                 tokens; use a decimal string or a bigint of attos instead"
+            ));
+        }
+        // The check above makes the conversion exact.
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let tokens = tokens as u64;
+        Ok(Self(Amount::from_tokens(tokens.into())))
+    }
+}
+
+#[cfg(feature = "web")]
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        // Bigints and numbers mean different units, which `serde` can't tell apart.
+        let value = serde_wasm_bindgen::preserve::deserialize(deserializer)?;
+        if let Some(tokens) = value.as_string() {
+            return Self::parse_tokens(&tokens).map_err(D::Error::custom);
+        }
+        if let Some(tokens) = value.as_f64() {
+            return Self::from_number(tokens).map_err(D::Error::custom);
+        }
+        if !value.is_bigint() {
+            return Err(D::Error::custom(
+                "Amounts must be decimal strings of tokens or bigints of attos",
+            ));
+        }
+        u128::try_from(value)
+            .map(|attos| Self(Amount::from_attos(attos)))
+            .map_err(|_| {
+                D::Error::custom(format!(
+                    "Invalid amount: amounts in attos must be between 0 and {}",
+                    u128::MAX
+                ))
+            })
+    }
+}
+
+#[cfg(not(feature = "web"))]
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Reads amounts outside of JS, where there are no bigints: strings are decimal
+/// tokens, numbers and integers of at most 64 bits are whole tokens, and wider
+/// integers are attos.
+#[cfg(not(feature = "web"))]
+struct Visitor;
+
+#[cfg(not(feature = "web"))]
+impl serde::de::Visitor<'_> for Visitor {
+    type Value = TokenAmount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal string or a whole number of tokens, or an integer of attos")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, tokens: &str) -> Result<TokenAmount, E> {
+        TokenAmount::parse_tokens(tokens).map_err(E::custom)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, tokens: u64) -> Result<TokenAmount, E> {
+        Ok(TokenAmount(Amount::from_tokens(tokens.into())))
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, tokens: i64) -> Result<TokenAmount, E> {
+        u64::try_from(tokens)
+            .map_err(|_| {
+                E::custom(format!(
+                    "Invalid amount {tokens}: amounts can't be negative"
+                ))
+            })
+            .and_then(|tokens| self.visit_u64(tokens))
+    }
+
+    fn visit_u128<E: serde::de::Error>(self, attos: u128) -> Result<TokenAmount, E> {
+        Ok(TokenAmount(Amount::from_attos(attos)))
+    }
+
+    fn visit_i128<E: serde::de::Error>(self, attos: i128) -> Result<TokenAmount, E> {
+        u128::try_from(attos)
+            .map_err(|_| E::custom(format!("Invalid amount {attos}: amounts can't be negative")))
+            .and_then(|attos| self.visit_u128(attos))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, tokens: f64) -> Result<TokenAmount, E> {
+        TokenAmount::from_number(tokens).map_err(E::custom)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "web"))]
+    use serde::de::Visitor as _;
+    #[cfg(feature = "web")]
+    use wasm_bindgen::JsValue;
+    #[cfg(feature = "web")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[cfg(not(feature = "web"))]
+    fn parse(json: &str) -> Result<Amount, serde_json::Error> {
+        serde_json::from_str::<TokenAmount>(json).map(Amount::from)
+    }
+
+    #[test]
+    #[cfg(not(feature = "web"))]
+    fn decimal_strings_are_tokens() {
+        assert_eq!(
+            parse(r#""0.125""#).unwrap(),
+            Amount::from_attos(125_000_000_000_000_000)
+        );
+        assert_eq!(
+            parse(r#""0.000000000000000001""#).unwrap(),
+            Amount::from_attos(1)
+        );
+        assert_eq!(parse(r#"" 2 ""#).unwrap(), Amount::from_tokens(2));
+    }
+
+    #[test]
+    #[cfg(not(feature = "web"))]
+    fn more_than_18_decimals_are_rejected() {
+        assert!(parse(r#""0.0000000000000000001""#).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "web"))]
+    fn negative_amounts_are_rejected() {
+        assert!(parse(r#""-1""#).is_err());
+        assert!(parse("-1").is_err());
+        assert!(Visitor.visit_i128::<serde_json::Error>(-1).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "web"))]
+    fn numbers_must_be_whole_and_safe() {
+        assert_eq!(parse("3").unwrap(), Amount::from_tokens(3));
+        assert!(parse("1.5").is_err());
+        assert!(TokenAmount::from_number(f64::NAN).is_err());
+        assert!(TokenAmount::from_number(f64::INFINITY).is_err());
+        assert_eq!(
+            TokenAmount::from_number(MAX_SAFE_INTEGER).unwrap().0,
+            Amount::from_tokens(9_007_199_254_740_991)
+        );
+        assert!(TokenAmount::from_number(MAX_SAFE_INTEGER + 1.0).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "web"))]
+    fn wide_integers_are_attos() {
+        assert_eq!(
+            Visitor.visit_u128::<serde_json::Error>(5).unwrap().0,
+            Amount::from_attos(5)
+        );
+        assert_eq!(
+            Visitor.visit_i128::<serde_json::Error>(5).unwrap().0,
+            Amount::from_attos(5)
+        );
+        assert_eq!(
+            Visitor
+                .visit_u128::<serde_json::Error>(u128::MAX)
+                .unwrap()
+                .0,
+            Amount::MAX
+        );
+    }
+
+    #[cfg(feature = "web")]
+    fn from_js(value: JsValue) -> Result<Amount, serde_wasm_bindgen::Error> {
+        serde_wasm_bindgen::from_value::<TokenAmount>(value).map(Amount::from)
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg(feature = "web")]
+    fn js_bigints_are_attos() {
+        assert_eq!(
+            from_js(JsValue::from(5_u64)).unwrap(),
+            Amount::from_attos(5)
+        );
+        assert_eq!(from_js(JsValue::from(u128::MAX)).unwrap(), Amount::MAX);
+        assert!(from_js(JsValue::from(-1_i64)).is_err());
+        // 2^128.
+        let too_large =
+            js_sys::BigInt::new(&"340282366920938463463374607431768211456".into()).unwrap();
+        assert!(from_js(too_large.into()).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg(feature = "web")]
+    fn js_numbers_are_whole_tokens() {
+        assert_eq!(
+            from_js(JsValue::from_f64(3.0)).unwrap(),
+            Amount::from_tokens(3)
+        );
+        assert!(from_js(JsValue::from_f64(1.5)).is_err());
+        assert!(from_js(JsValue::from_f64(-1.0)).is_err());
+        assert!(from_js(JsValue::from_f64(MAX_SAFE_INTEGER + 1.0)).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg(feature = "web")]
+    fn js_strings_are_decimal_tokens() {
+        assert_eq!(
+            from_js(JsValue::from_str("0.125")).unwrap(),
+            Amount::from_attos(125_000_000_000_000_000)
+        );
+        assert!(from_js(JsValue::from_str("0.0000000000000000001")).is_err());
+        assert!(from_js(JsValue::from_str("-1")).is_err());
+        assert!(from_js(JsValue::TRUE).is_err());
+    }
+}
@@ -4,7 +4,6 @@
 use linera_base::identifiers::{AccountOwner, ApplicationId};
 use linera_core::client::ChainClient;
 use wasm_bindgen::prelude::*;
-use web_sys::wasm_bindgen;
 
 use crate::{Client, Environment, JsResult};
 
@@ -34,7 +33,8 @@ impl Application {
     ///
     /// # Errors
     /// If the application ID is invalid, the query is incorrect, or
-    /// the response isn't valid UTF-8.
+    /// the response isn't valid UTF-8. If the query's operations can't be signed, the
+    /// error is a `SignerError` telling e.g. whether the user rejected signing.
     ///
     /// # Panics
     /// On internal protocol errors.
@@ -44,7 +44,7 @@ impl Application {
     pub async fn query(&self, query: &str, options: Option<QueryOptions>) -> JsResult<String> {
         tracing::debug!("querying application: {query}");
         let QueryOptions { block_hash, owner } = options.unwrap_or_default();
-        let mut chain_client = self.chain_client.clone();
+        let mut chain_client = self.client.with_wallet_owner(&self.chain_client);
         if let Some(owner) = owner {
             chain_client.set_preferred_owner(owner);
         }
@@ -70,6 +70,8 @@ impl Application {
         };
 
         if !operations.is_empty() {
+            let _approval =
+                super::approval::approve(&self.client, &mut chain_client, &operations).await?;
             let _hash = self
                 .client
                 .client_context
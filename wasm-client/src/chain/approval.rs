@@ -0,0 +1,338 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Approval of blocks before they are signed.
+//!
+//! A client may have an approval hook, set with `Client.setApprovalHook`. Before a
+//! block with operations is proposed, the hook is given the block, with its operations
+//! decoded along with the incoming message bundles it receives, and the block is only
+//! signed if the hook approves it. Application operations are opaque bytes to the
+//! client, so they are given as hex. `Chain.processInbox` and `Chain.rejectMessages`
+//! submit the bundles they are about to receive or reject in the same way.
+//!
+//! While a hook is set, blocks with operations leave the inbox alone, so that they
+//! are exactly the blocks approved; the chain listener or `processInbox` receives the
+//! messages. The blocks receiving messages can't be known in advance: bundles may
+//! arrive while the hook runs. So the signer checks every block a call proposes
+//! against what the hook approved, and submits it to the hook again if it differs.
+//!
+//! Blocks the chain listener proposes on its own, which only receive incoming
+//! messages, aren't submitted to the hook.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use futures::future::LocalBoxFuture;
+use linera_base::{
+    crypto::{AccountSignature, CryptoHash, Signer as _},
+    identifiers::{Account, AccountOwner, ApplicationId, ChainId},
+};
+use linera_chain::data_types::{IncomingBundle, ProposedBlock};
+use linera_core::client::{BlanketMessagePolicy, ChainClient, MessagePolicy};
+use linera_execution::{system::SystemOperation, Operation};
+use serde::Serialize;
+
+use super::{history::ReceivedBundle, WeightedOwner};
+use crate::{signer, Client, Environment, JsResult};
+
+/// A function approving blocks before they are signed: it is given the block, and
+/// resolves to whether to sign it.
+pub type ApprovalHook =
+    Rc<dyn Fn(PendingBlock) -> LocalBoxFuture<'static, Result<bool, signer::Error>>>;
+
+/// A block about to be signed, as given to the approval hook.
+#[derive(Serialize, tsify::Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingBlock {
+    /// The chain the block is proposed on.
+    pub chain_id: ChainId,
+    /// The owner signing the block.
+    pub owner: AccountOwner,
+    pub operations: Vec<DecodedOperation>,
+    /// The message bundles from other chains the block receives or rejects, per the
+    /// chain's message policy.
+    pub incoming: Vec<ReceivedBundle>,
+}
+
+/// An operation of a block, decoded for display.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DecodedOperation {
+    /// A transfer of tokens.
+    Transfer {
+        /// The account the tokens are taken from, or `CHAIN` for the chain balance.
+        owner: AccountOwner,
+        recipient: Account,
+        /// The amount, in tokens.
+        amount: String,
+    },
+    /// A change of the owners of the chain.
+    #[serde(rename_all = "camelCase")]
+    ChangeOwnership {
+        super_owners: Vec<AccountOwner>,
+        owners: Vec<WeightedOwner>,
+    },
+    /// Any other system operation, described in Rust syntax.
+    System { description: String },
+    /// An operation of an application, in the application's own encoding.
+    #[serde(rename_all = "camelCase")]
+    User {
+        application_id: ApplicationId,
+        /// The operation's bytes, as hex.
+        bytes: String,
+    },
+}
+
+impl From<&Operation> for DecodedOperation {
+    fn from(operation: &Operation) -> Self {
+        match operation {
+            Operation::System(operation) => match operation.as_ref() {
+                SystemOperation::Transfer {
+                    owner,
+                    recipient,
+                    amount,
+                } => DecodedOperation::Transfer {
+                    owner: *owner,
+                    recipient: *recipient,
+                    amount: amount.to_string(),
+                },
+                SystemOperation::ChangeOwnership {
+                    super_owners,
+                    owners,
+                    ..
+                } => DecodedOperation::ChangeOwnership {
+                    super_owners: super_owners.clone(),
+                    owners: owners
+                        .iter()
+                        .map(|(owner, weight)| WeightedOwner {
+                            owner: *owner,
+                            weight: *weight,
+                        })
+                        .collect(),
+                },
+                operation => DecodedOperation::System {
+                    description: format!("{operation:?}"),
+                },
+            },
+            Operation::User {
+                application_id,
+                bytes,
+            } => DecodedOperation::User {
+                application_id: *application_id,
+                bytes: hex::encode(bytes),
+            },
+        }
+    }
+}
+
+impl PendingBlock {
+    fn new<'a>(
+        chain_id: ChainId,
+        owner: AccountOwner,
+        operations: impl IntoIterator<Item = &'a Operation>,
+        incoming: impl IntoIterator<Item = &'a IncomingBundle>,
+    ) -> Self {
+        PendingBlock {
+            chain_id,
+            owner,
+            operations: operations.into_iter().map(DecodedOperation::from).collect(),
+            incoming: incoming.into_iter().map(ReceivedBundle::from).collect(),
+        }
+    }
+}
+
+/// What the approval hook approved for a call proposing blocks.
+struct Approved {
+    /// The client proposing the blocks.
+    chain_client: ChainClient<Environment>,
+    operations: Vec<Operation>,
+    incoming: Vec<IncomingBundle>,
+    /// The blocks approved when the signer submitted them again.
+    blocks: Vec<ProposedBlock>,
+}
+
+impl Approved {
+    /// Returns whether `block` is one the hook approved: with the approved operations,
+    /// if any, and only receiving approved bundles.
+    fn covers(&self, block: &ProposedBlock) -> bool {
+        let approved = block.operations().eq(&self.operations)
+            && block
+                .incoming_bundles()
+                .all(|bundle| self.incoming.contains(bundle));
+        approved || self.blocks.contains(block)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    hook: Option<ApprovalHook>,
+    /// What the hook approved for the calls proposing blocks, by call.
+    approved: BTreeMap<u64, Approved>,
+    next_id: u64,
+}
+
+/// The approval hook of a client, shared with its signer.
+#[derive(Clone, Default)]
+pub(crate) struct Approvals(Rc<RefCell<State>>);
+
+impl Approvals {
+    pub(crate) fn set_hook(&self, hook: Option<ApprovalHook>) {
+        self.0.borrow_mut().hook = hook;
+    }
+
+    fn hook(&self) -> Option<ApprovalHook> {
+        self.0.borrow().hook.clone()
+    }
+
+    /// Submits to the hook the blocks about to be signed for `owner` that it didn't
+    /// approve.
+    async fn check(&self, owner: &AccountOwner) -> Result<(), signer::Error> {
+        let Some(hook) = self.hook() else {
+            return Ok(());
+        };
+        let calls = self
+            .0
+            .borrow()
+            .approved
+            .iter()
+            .map(|(id, approved)| (*id, approved.chain_client.clone()))
+            .collect::<Vec<_>>();
+        for (id, chain_client) in calls {
+            // The block being signed is the pending proposal of its chain.
+            let Some(pending) = chain_client.pending_proposal() else {
+                continue;
+            };
+            let block = pending.block;
+            let covered = self
+                .0
+                .borrow()
+                .approved
+                .get(&id)
+                .is_none_or(|approved| approved.covers(&block));
+            if covered || block.authenticated_signer != Some(*owner) {
+                continue;
+            }
+            let shown = PendingBlock::new(
+                block.chain_id,
+                *owner,
+                block.operations(),
+                block.incoming_bundles(),
+            );
+            if !hook(shown).await? {
+                return Err(rejected());
+            }
+            if let Some(approved) = self.0.borrow_mut().approved.get_mut(&id) {
+                approved.blocks.push(block);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the hook approved for a call, which the signer checks the blocks against
+/// until it is dropped.
+pub(crate) struct Approval {
+    approvals: Approvals,
+    id: u64,
+}
+
+impl Drop for Approval {
+    fn drop(&mut self) {
+        self.approvals.0.borrow_mut().approved.remove(&self.id);
+    }
+}
+
+fn rejected() -> signer::Error {
+    signer::Error::UserRejected {
+        message: "The block was rejected by the approval hook".to_string(),
+    }
+}
+
+/// Submits a block with `operations` on the chain of `chain_client`, along with the
+/// bundles waiting in its inbox if there are no operations, to the approval hook of
+/// `client`, if it has one. A block with neither is never proposed, and isn't
+/// submitted.
+///
+/// A block with operations receives no bundles: `chain_client` is set to leave them
+/// alone. The blocks `chain_client` proposes are checked against the approved one
+/// until the returned `Approval` is dropped.
+///
+/// # Errors
+/// If the hook rejects the block, with a `SignerError` of kind `userRejected`, or
+/// with the hook's error if it fails.
+pub(crate) async fn approve(
+    client: &Client,
+    chain_client: &mut ChainClient<Environment>,
+    operations: &[Operation],
+) -> JsResult<Option<Approval>> {
+    let approvals = &client.approvals;
+    let Some(hook) = approvals.hook() else {
+        return Ok(None);
+    };
+    let incoming = if operations.is_empty() {
+        chain_client.pending_message_bundles().await?
+    } else {
+        chain_client.options_mut().message_policy =
+            MessagePolicy::new(BlanketMessagePolicy::Ignore, None);
+        Vec::new()
+    };
+    if operations.is_empty() && incoming.is_empty() {
+        return Ok(None);
+    }
+    let block = PendingBlock::new(
+        chain_client.chain_id(),
+        chain_client.identity().await?,
+        operations,
+        &incoming,
+    );
+    if !hook(block).await? {
+        return Err(rejected().into());
+    }
+    let mut state = approvals.0.borrow_mut();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.approved.insert(
+        id,
+        Approved {
+            chain_client: chain_client.clone(),
+            operations: operations.to_vec(),
+            incoming,
+            blocks: Vec::new(),
+        },
+    );
+    Ok(Some(Approval {
+        approvals: approvals.clone(),
+        id,
+    }))
+}
+
+/// The signer of a client, submitting the blocks it is asked to sign to the approval
+/// hook again if they aren't the ones approved.
+#[derive(Clone)]
+pub struct ApprovingSigner {
+    signer: signer::Signer,
+    approvals: Approvals,
+}
+
+impl ApprovingSigner {
+    pub(crate) fn new(signer: signer::Signer, approvals: Approvals) -> Self {
+        Self { signer, approvals }
+    }
+}
+
+impl linera_base::crypto::Signer for ApprovingSigner {
+    type Error = signer::Error;
+
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        self.signer.contains_key(owner).await
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Self::Error> {
+        self.approvals.check(owner).await?;
+        self.signer.sign(owner, value).await
+    }
+}
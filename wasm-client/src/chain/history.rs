@@ -0,0 +1,256 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The history of a chain: its blocks, newest first, with their operations and the
+//! messages they received decoded.
+//!
+//! Blocks are read from the local chain state, which holds the blocks of the chains
+//! the client synchronized. Pages are delimited by an opaque cursor: the index in the
+//! local log of confirmed blocks below which the next page starts. It is not a block
+//! height, and only means something to the client that returned it.
+
+use linera_base::{
+    crypto::CryptoHash,
+    data_types::BlockHeight,
+    identifiers::{AccountOwner, ApplicationId, ChainId},
+};
+use linera_chain::data_types::{IncomingBundle, MessageAction};
+use linera_execution::{system::SystemMessage, Message};
+use linera_storage::Storage as _;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use super::{Chain, DecodedOperation};
+use crate::{JsError, JsResult};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+/// Options of `Chain.history`.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+pub struct HistoryOptions {
+    /// The cursor of the page to read, from a previous page. Defaults to the newest
+    /// blocks.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// How many blocks to read, at most 100, including the pending one. Defaults to 20.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Whether a block of the history is final.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockStatus {
+    /// The block is confirmed by the validators.
+    Confirmed,
+    /// The block was proposed by this client, but isn't confirmed yet.
+    Pending,
+}
+
+/// A message received by a block, decoded for display.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DecodedMessage {
+    /// Tokens credited to an account of the chain.
+    Credit {
+        source: AccountOwner,
+        target: AccountOwner,
+        /// The amount, in tokens.
+        amount: String,
+    },
+    /// Any other system message, described in Rust syntax.
+    System { description: String },
+    /// A message of an application, in the application's own encoding.
+    #[serde(rename_all = "camelCase")]
+    User {
+        application_id: ApplicationId,
+        /// The message's bytes, as hex.
+        bytes: String,
+    },
+}
+
+impl From<&Message> for DecodedMessage {
+    fn from(message: &Message) -> Self {
+        match message {
+            Message::System(SystemMessage::Credit {
+                source,
+                target,
+                amount,
+            }) => DecodedMessage::Credit {
+                source: *source,
+                target: *target,
+                amount: amount.to_string(),
+            },
+            Message::System(message) => DecodedMessage::System {
+                description: format!("{message:?}"),
+            },
+            Message::User {
+                application_id,
+                bytes,
+            } => DecodedMessage::User {
+                application_id: *application_id,
+                bytes: hex::encode(bytes),
+            },
+        }
+    }
+}
+
+/// How a message is delivered, which tells what happens if it is rejected.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageKind {
+    /// The message is discarded if rejected.
+    Simple,
+    /// The message can't be rejected.
+    Protected,
+    /// The message bounces back to its sender if rejected, e.g. returning the tokens
+    /// of a transfer.
+    Tracked,
+    /// A tracked message bouncing back after being rejected.
+    Bouncing,
+}
+
+impl From<linera_execution::MessageKind> for MessageKind {
+    fn from(kind: linera_execution::MessageKind) -> Self {
+        match kind {
+            linera_execution::MessageKind::Simple => MessageKind::Simple,
+            linera_execution::MessageKind::Protected => MessageKind::Protected,
+            linera_execution::MessageKind::Tracked => MessageKind::Tracked,
+            linera_execution::MessageKind::Bouncing => MessageKind::Bouncing,
+        }
+    }
+}
+
+/// A message of a bundle, with how it is delivered.
+#[derive(Serialize, tsify::Tsify)]
+pub struct ReceivedMessage {
+    pub kind: MessageKind,
+    #[serde(flatten)]
+    pub message: DecodedMessage,
+}
+
+/// Messages from another chain received by a block.
+#[derive(Serialize, tsify::Tsify)]
+pub struct ReceivedBundle {
+    /// The chain the messages come from.
+    pub origin: ChainId,
+    /// The height of the block of `origin` that sent the messages.
+    pub height: BlockHeight,
+    /// Whether the messages are executed, rather than rejected.
+    pub accepted: bool,
+    pub messages: Vec<ReceivedMessage>,
+}
+
+impl From<&IncomingBundle> for ReceivedBundle {
+    fn from(incoming: &IncomingBundle) -> Self {
+        Self {
+            origin: incoming.origin,
+            height: incoming.bundle.height,
+            accepted: matches!(incoming.action, MessageAction::Accept),
+            messages: incoming
+                .bundle
+                .messages
+                .iter()
+                .map(|posted| ReceivedMessage {
+                    kind: posted.kind.into(),
+                    message: DecodedMessage::from(&posted.message),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A block of the history of a chain.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub height: BlockHeight,
+    /// When the block was made, in microseconds since the Unix epoch.
+    pub timestamp_micros: u64,
+    /// The hash of the block, if it is confirmed.
+    pub hash: Option<CryptoHash>,
+    pub status: BlockStatus,
+    /// The owner who signed the block, if any.
+    pub signer: Option<AccountOwner>,
+    pub operations: Vec<DecodedOperation>,
+    pub incoming_bundles: Vec<ReceivedBundle>,
+}
+
+/// A page of the history of a chain.
+#[derive(Serialize, tsify::Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct HistoryPage {
+    /// The blocks, newest first.
+    pub entries: Vec<HistoryEntry>,
+    /// The cursor of the next page, if there are older blocks.
+    pub cursor: Option<String>,
+}
+
+#[wasm_bindgen]
+impl Chain {
+    /// Reads a page of the history of the chain, newest blocks first. The first page
+    /// starts with the block this client proposed, if it isn't confirmed yet.
+    ///
+    /// # Errors
+    /// If the cursor is invalid, or if the chain state can't be read.
+    pub async fn history(&self, options: Option<HistoryOptions>) -> JsResult<HistoryPage> {
+        let HistoryOptions { cursor, limit } = options.unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let mut entries = Vec::with_capacity(limit);
+        if cursor.is_none() {
+            if let Some(pending) = self.chain_client.pending_proposal() {
+                let block = &pending.block;
+                entries.push(HistoryEntry {
+                    height: block.height,
+                    timestamp_micros: block.timestamp.micros(),
+                    hash: None,
+                    status: BlockStatus::Pending,
+                    signer: block.authenticated_signer,
+                    operations: block.operations().map(DecodedOperation::from).collect(),
+                    incoming_bundles: block.incoming_bundles().map(ReceivedBundle::from).collect(),
+                });
+            }
+        }
+        let (start, hashes) = {
+            let chain = self.chain_client.chain_state_view().await?;
+            let count = chain.confirmed_log.count();
+            let end = match &cursor {
+                Some(cursor) => cursor
+                    .parse::<usize>()
+                    .map_err(|_| JsError::new(&format!("Invalid history cursor {cursor:?}")))?
+                    .min(count),
+                None => count,
+            };
+            let start = end.saturating_sub(limit - entries.len());
+            (start, chain.confirmed_log.read(start..end).await?)
+        };
+        let storage = self.chain_client.storage_client();
+        for hash in hashes.into_iter().rev() {
+            let confirmed = storage.read_confirmed_block(hash).await?;
+            let block = confirmed.block();
+            entries.push(HistoryEntry {
+                height: block.header.height,
+                timestamp_micros: block.header.timestamp.micros(),
+                hash: Some(hash),
+                status: BlockStatus::Confirmed,
+                signer: block.header.authenticated_signer,
+                operations: block
+                    .body
+                    .operations()
+                    .map(DecodedOperation::from)
+                    .collect(),
+                incoming_bundles: block
+                    .body
+                    .incoming_bundles()
+                    .map(ReceivedBundle::from)
+                    .collect(),
+            });
+        }
+        Ok(HistoryPage {
+            entries,
+            cursor: (start > 0).then(|| start.to_string()),
+        })
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The inbox of a chain: the messages other chains sent it, which it hasn't received
+//! in a block yet.
+//!
+//! The chain listener receives them automatically, unless the client was created with
+//! `autoProcessInbox: false`. In that case they wait in the inbox, where they can be
+//! reviewed with `pendingMessages`, then received with `processInbox` or rejected with
+//! `rejectMessages`: the other blocks the client proposes, e.g. for transfers, leave
+//! them alone.
+
+use std::collections::HashSet;
+
+use linera_base::identifiers::ChainId;
+use linera_client::chain_listener::ClientContext as _;
+use linera_core::client::{BlanketMessagePolicy, ChainClient, MessagePolicy};
+use wasm_bindgen::prelude::*;
+
+use super::{approval, history::ReceivedBundle, Chain};
+use crate::{Environment, JsResult};
+
+impl Chain {
+    /// Returns a client of the chain accepting the messages of its inbox, as
+    /// `processInbox` does, whatever the client's own message policy.
+    pub(crate) fn inbox_client(&self) -> ChainClient<Environment> {
+        let mut chain_client = self.proposer();
+        chain_client.options_mut().message_policy =
+            MessagePolicy::new(BlanketMessagePolicy::Accept, None);
+        chain_client
+    }
+
+    /// Lists the message bundles waiting in the inbox of the chain, oldest first.
+    ///
+    /// # Errors
+    /// If the chain state can't be read.
+    pub async fn pending_bundles(&self) -> JsResult<Vec<ReceivedBundle>> {
+        Ok(self
+            .inbox_client()
+            .pending_message_bundles()
+            .await?
+            .iter()
+            .map(ReceivedBundle::from)
+            .collect())
+    }
+}
+
+#[wasm_bindgen]
+impl Chain {
+    /// Receives the messages waiting in the inbox of the chain, proposing as many
+    /// blocks as needed, and returns the hashes of the blocks.
+    ///
+    /// # Errors
+    /// If the approval hook rejects the messages, with a `SignerError` of kind
+    /// `userRejected`, if the blocks can't be proposed, or if the wallet can't be
+    /// saved.
+    #[wasm_bindgen(js_name = processInbox)]
+    pub async fn process_inbox(&self) -> JsResult<Vec<String>> {
+        self.receive(self.inbox_client()).await
+    }
+
+    /// Rejects the messages from `origin` waiting in the inbox of the chain, proposing
+    /// as many blocks as needed, and returns the hashes of the blocks.
+    ///
+    /// Tracked messages bounce back to `origin`, returning the tokens of transfers, and
+    /// simple messages are discarded. Protected messages can't be rejected: they are
+    /// received as usual.
+    ///
+    /// # Errors
+    /// If the approval hook rejects the messages, with a `SignerError` of kind
+    /// `userRejected`, if the blocks can't be proposed, or if the wallet can't be
+    /// saved.
+    #[wasm_bindgen(js_name = rejectMessages)]
+    pub async fn reject_messages(&self, origin: ChainId) -> JsResult<Vec<String>> {
+        // The policy only applies to this clone, leaving the other messages alone.
+        let mut chain_client = self.proposer();
+        chain_client.options_mut().message_policy =
+            MessagePolicy::new(BlanketMessagePolicy::Reject, Some(HashSet::from([origin])));
+        self.receive(chain_client).await
+    }
+}
+
+impl Chain {
+    /// Submits the bundles waiting in the inbox to the approval hook, then processes
+    /// the inbox with `chain_client`'s message policy, and saves the wallet.
+    async fn receive(&self, mut chain_client: ChainClient<Environment>) -> JsResult<Vec<String>> {
+        let _approval = approval::approve(&self.client, &mut chain_client, &[]).await?;
+        // Holding the context keeps the chain listener from processing the inbox
+        // meanwhile.
+        let mut context = self.client.client_context.lock().await;
+        let result = chain_client.process_inbox().await;
+        context.update_wallet(&chain_client).await?;
+        drop(context);
+        let (certificates, _timeout) = result?;
+        self.client.persistent.save_to_storage(false).await?;
+        Ok(certificates
+            .iter()
+            .map(|certificate| certificate.hash().to_string())
+            .collect())
+    }
+}
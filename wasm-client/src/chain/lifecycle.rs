@@ -0,0 +1,179 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opening child chains and closing chains.
+//!
+//! A child chain is opened by a block of its parent, which funds it, so no faucet is
+//! needed. It is added to the wallet, signed for by the first of its owners the signer
+//! holds a key for.
+
+use std::collections::BTreeMap;
+
+use linera_base::{
+    crypto::Signer as _,
+    data_types::Amount,
+    identifiers::{AccountOwner, ApplicationId},
+    ownership::{ApplicationPermissions, ChainOwnership, TimeoutConfig},
+};
+use linera_client::chain_listener::{ClientContext as _, ListenerCommand};
+use linera_core::wallet;
+use linera_execution::{
+    system::{OpenChainConfig, SystemOperation},
+    Operation,
+};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use super::{approval, Chain, TokenAmount};
+use crate::{wallet::CreatedVia, JsError, JsResult};
+
+/// The weight of each owner of a chain opened with several owners.
+const OWNER_WEIGHT: u64 = 100;
+/// The number of multi-leader rounds of a chain opened with several owners.
+const MULTI_LEADER_ROUNDS: u32 = 10;
+
+/// Which applications a chain lets do what. Omitted fields keep the defaults, which
+/// put no restrictions on the applications.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPermissions {
+    /// The applications whose operations blocks may contain, or all if omitted.
+    #[serde(default)]
+    pub execute_operations: Option<Vec<ApplicationId>>,
+    /// Applications every block must have an operation of.
+    #[serde(default)]
+    pub mandatory_applications: Vec<ApplicationId>,
+    /// Applications allowed to close the chain.
+    #[serde(default)]
+    pub close_chain: Vec<ApplicationId>,
+    /// Applications allowed to change these permissions.
+    #[serde(default)]
+    pub change_application_permissions: Vec<ApplicationId>,
+}
+
+impl From<AppPermissions> for ApplicationPermissions {
+    fn from(permissions: AppPermissions) -> Self {
+        Self {
+            execute_operations: permissions.execute_operations,
+            mandatory_applications: permissions.mandatory_applications,
+            close_chain: permissions.close_chain,
+            change_application_permissions: permissions.change_application_permissions,
+            ..Self::default()
+        }
+    }
+}
+
+/// Parameters of `Chain.openChain`.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenChainParams {
+    /// The owners of the new chain. Defaults to the key this chain is signed with.
+    #[serde(default)]
+    pub owners: Option<Vec<AccountOwner>>,
+    /// The tokens moved from this chain's balance to the new chain: a decimal string
+    /// of tokens, a bigint of attos or a whole number of tokens. Defaults to none.
+    #[serde(default)]
+    #[tsify(type = "string | bigint | number")]
+    pub balance: Option<TokenAmount>,
+    #[serde(default)]
+    pub application_permissions: Option<AppPermissions>,
+}
+
+#[wasm_bindgen]
+impl Chain {
+    /// Opens a child chain funded from this chain's balance, adds it to the wallet and
+    /// returns its ID. The block opening it is subject to approval.
+    ///
+    /// With a single owner, the new chain has that owner alone; with several, they
+    /// have equal weights.
+    ///
+    /// # Errors
+    /// If `owners` is empty, if the balance is too low, if the block can't be
+    /// submitted, or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = openChain)]
+    pub async fn open_chain(&self, params: Option<OpenChainParams>) -> JsResult<String> {
+        let OpenChainParams {
+            owners,
+            balance,
+            application_permissions,
+        } = params.unwrap_or_default();
+        let mut proposer = self.proposer();
+        let owners = match owners {
+            Some(owners) => owners,
+            None => vec![proposer.identity().await?],
+        };
+        let ownership = match owners.as_slice() {
+            [] => return Err(JsError::new("A chain needs at least one owner")),
+            [owner] => ChainOwnership::single(*owner),
+            _ => ChainOwnership::multiple(
+                owners.iter().map(|owner| (*owner, OWNER_WEIGHT)),
+                MULTI_LEADER_ROUNDS,
+                TimeoutConfig::default(),
+            ),
+        };
+        let balance = balance.map_or(Amount::ZERO, Amount::from);
+        let application_permissions =
+            ApplicationPermissions::from(application_permissions.unwrap_or_default());
+
+        let _approval = approval::approve(
+            &self.client,
+            &mut proposer,
+            &[Operation::system(SystemOperation::OpenChain(
+                OpenChainConfig {
+                    ownership: ownership.clone(),
+                    balance,
+                    application_permissions: application_permissions.clone(),
+                },
+            ))],
+        )
+        .await?;
+        let (description, _certificate) = self
+            .client
+            .client_context
+            .lock()
+            .await
+            .apply_client_command(&proposer, |chain_client| {
+                chain_client.open_chain(ownership.clone(), application_permissions.clone(), balance)
+            })
+            .await?;
+
+        let mut owner = None;
+        for candidate in owners {
+            if self.chain_client.signer().contains_key(&candidate).await? {
+                owner = Some(candidate);
+                break;
+            }
+        }
+        let chain_id = description.id();
+        let persistent = &self.client.persistent;
+        persistent.wallet.chains.insert(
+            chain_id,
+            wallet::Chain {
+                owner,
+                ..description.into()
+            },
+        );
+        persistent
+            .wallet
+            .set_created_via(chain_id, CreatedVia::Opened);
+        persistent.save_to_storage(false).await?;
+        // This only fails if the background listener has already stopped.
+        let _ = self
+            .client
+            .listener_commands
+            .send(ListenerCommand::Listen(BTreeMap::from([(chain_id, owner)])));
+        Ok(chain_id.to_string())
+    }
+
+    /// Closes the chain, subject to approval. A closed chain can't propose blocks
+    /// anymore, and tracked messages sent to it bounce back; it stays in the wallet
+    /// until it is unassigned.
+    ///
+    /// # Errors
+    /// If the chain is already closed, or if the block can't be submitted.
+    pub async fn close(&self) -> JsResult<()> {
+        self.submit(vec![Operation::system(SystemOperation::CloseChain)])
+            .await
+    }
+}
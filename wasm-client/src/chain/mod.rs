@@ -1,24 +1,45 @@
 // Copyright (c) Zefchain Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use futures::stream::{AbortHandle, Abortable, StreamExt};
-use linera_base::{data_types::BlockHeight, identifiers::AccountOwner};
+use linera_base::{
+    data_types::{Amount, BlockHeight},
+    identifiers::{Account, AccountOwner},
+};
+use linera_chain::data_types::MessageAction;
 use linera_client::chain_listener::ClientContext as _;
 use linera_core::{
     client::ChainClient,
     node::{ValidatorNode as _, ValidatorNodeProvider as _},
-    worker::Reason,
+    worker::{Notification, Reason},
+};
+use linera_execution::{
+    system::{SystemMessage, SystemOperation},
+    Message, Operation,
 };
-use serde::ser::Serialize as _;
 use wasm_bindgen::prelude::*;
-use web_sys::{js_sys, wasm_bindgen};
 
-use crate::{Client, Environment, JsResult};
+use crate::{Client, Environment, JsError, JsResult};
 
+pub mod amount;
+pub use amount::TokenAmount;
 pub mod application;
 pub use application::Application;
+pub mod approval;
+pub use approval::{DecodedOperation, PendingBlock};
+pub mod history;
+pub use history::{HistoryEntry, HistoryOptions, HistoryPage};
+pub mod inbox;
+pub mod lifecycle;
+pub use lifecycle::{AppPermissions, OpenChainParams};
+pub mod ownership;
+pub use ownership::{OwnershipInfo, RoundOptions, WeightedOwner};
+
+/// How many times `Chain::balances` reads the chain before giving up, if blocks keep
+/// being added while it reads.
+const BALANCES_ATTEMPTS: usize = 3;
 
 #[wasm_bindgen]
 pub struct Chain {
@@ -31,8 +52,35 @@ pub struct Chain {
 pub struct TransferParams {
     #[serde(default)]
     pub donor: Option<AccountOwner>,
-    pub amount: u64,
-    pub recipient: linera_base::identifiers::Account,
+    /// A decimal string of tokens, a bigint of attos or a whole number of tokens.
+    #[tsify(type = "string | bigint | number")]
+    pub amount: TokenAmount,
+    pub recipient: Recipient,
+}
+
+/// The account receiving a transfer.
+#[derive(serde::Deserialize, tsify::Tsify)]
+pub struct Recipient {
+    /// The chain ID, or the label of a chain of the wallet.
+    pub chain_id: String,
+    pub owner: AccountOwner,
+}
+
+/// The balance of an account of a chain, as a decimal string of tokens.
+#[derive(serde::Serialize, tsify::Tsify)]
+pub struct AccountBalance {
+    pub owner: AccountOwner,
+    pub balance: String,
+}
+
+/// The balances of a chain, as decimal strings of tokens.
+#[derive(serde::Serialize, tsify::Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct Balances {
+    /// The chain's own balance, which transfers without a donor are taken from.
+    pub chain: String,
+    /// The accounts of the chain with a non-zero balance.
+    pub accounts: Vec<AccountBalance>,
 }
 
 #[derive(Default, serde::Deserialize, tsify::Tsify)]
@@ -55,23 +103,21 @@ impl NotificationHandle {
     }
 }
 
-#[wasm_bindgen]
 impl Chain {
-    /// Sets a callback to be called when a notification is received
-    /// from the network.
+    /// Calls `handler` with the notification of every new block of the chain, until
+    /// the returned handle is unsubscribed.
     ///
     /// # Errors
     /// If we fail to subscribe to the notification stream.
-    ///
-    /// # Panics
-    /// If the handler function fails.
-    #[wasm_bindgen(js_name = onNotification)]
-    pub fn on_notification(&self, handler: js_sys::Function) -> JsResult<NotificationHandle> {
+    pub fn on_new_block(
+        &self,
+        handler: impl Fn(&Notification) + 'static,
+    ) -> JsResult<NotificationHandle> {
         let mut notifications = self.chain_client.subscribe()?;
         let mut last_height: Option<BlockHeight> = None;
         let (abort_handle, abort_reg) = AbortHandle::new_pair();
 
-        wasm_bindgen_futures::spawn_local(async move {
+        crate::runtime::spawn_local(async move {
             let fut = async move {
                 while let Some(notification) = notifications.next().await {
                     if let Reason::NewBlock { height, .. } = notification.reason {
@@ -79,13 +125,7 @@ impl Chain {
                             continue;
                         }
                         last_height = Some(height);
-
-                        handler
-                            .call1(
-                                &JsValue::null(),
-                                &serde_wasm_bindgen::to_value(&notification).unwrap(),
-                            )
-                            .unwrap_throw();
+                        handler(&notification);
                     }
                 }
             };
@@ -100,6 +140,15 @@ impl Chain {
         Ok(NotificationHandle { abort_handle })
     }
 
+    /// Returns the height of the next block of the chain, as stored locally.
+    async fn next_block_height(&self) -> JsResult<BlockHeight> {
+        let state = self.chain_client.chain_state_view().await?;
+        Ok(state.tip_state.get().next_block_height)
+    }
+}
+
+#[wasm_bindgen]
+impl Chain {
     /// Transfers funds from one account to another.
     ///
     /// `options` should be an options object of the form `{ donor,
@@ -107,25 +156,27 @@ impl Chain {
     /// come from the chain balance.
     ///
     /// # Errors
-    /// - if the options object is of the wrong form
+    /// - if the options object is of the wrong form, or the recipient chain label is
+    ///   unknown
     /// - if the transfer fails
+    /// - if signing fails, with a `SignerError` telling e.g. whether the user
+    ///   rejected signing
     #[wasm_bindgen]
     pub async fn transfer(&self, params: TransferParams) -> JsResult<()> {
-        let _hash = self
-            .client
-            .client_context
-            .lock()
-            .await
-            .apply_client_command(&self.chain_client, |_chain_client| {
-                self.chain_client.transfer(
-                    params.donor.unwrap_or(AccountOwner::CHAIN),
-                    linera_base::data_types::Amount::from_tokens(params.amount.into()),
-                    params.recipient,
-                )
-            })
-            .await?;
-
-        Ok(())
+        let recipient = Account {
+            chain_id: self
+                .client
+                .persistent
+                .wallet
+                .resolve(&params.recipient.chain_id)?,
+            owner: params.recipient.owner,
+        };
+        self.submit(vec![Operation::system(SystemOperation::Transfer {
+            owner: params.donor.unwrap_or(AccountOwner::CHAIN),
+            recipient,
+            amount: params.amount.into(),
+        })])
+        .await
     }
 
     /// Gets the balance of the default chain.
@@ -133,7 +184,7 @@ impl Chain {
     /// # Errors
     /// If the chain couldn't be established.
     pub async fn balance(&self) -> JsResult<String> {
-        match self.chain_client.query_balance().await {
+        match self.inbox_client().query_balance().await {
             Ok(balance) => Ok(balance.to_string()),
             Err(e) => {
                 tracing::error!("failed to query balance: {:?}", e);
@@ -142,12 +193,90 @@ impl Chain {
         }
     }
 
+    /// Gets the balance of the account of `owner` on the chain, including incoming
+    /// transfers not yet received.
+    ///
+    /// # Errors
+    /// If the chain couldn't be established.
+    #[wasm_bindgen(js_name = ownerBalance)]
+    pub async fn owner_balance(&self, owner: AccountOwner) -> JsResult<String> {
+        Ok(self
+            .inbox_client()
+            .query_owner_balance(owner)
+            .await?
+            .to_string())
+    }
+
+    /// Gets the balance of the chain along with every account of the chain holding
+    /// tokens, including incoming transfers not yet received. Unlike `balance`, the
+    /// fees of receiving the transfers aren't deducted.
+    ///
+    /// # Errors
+    /// If the chain couldn't be established, or kept adding blocks while it was
+    /// read.
+    pub async fn balances(&self) -> JsResult<Balances> {
+        // Staging a block only reports the balance of the chain and of one owner, so
+        // all balances are read from the chain state, with the incoming transfers
+        // credited here. A block added between reading the inbox and the state would
+        // have its transfers counted twice, so the reads are repeated until none was.
+        let inbox_client = self.inbox_client();
+        for _ in 0..BALANCES_ATTEMPTS {
+            let height = self.next_block_height().await?;
+            let bundles = inbox_client.pending_message_bundles().await?;
+            let (mut chain, mut balances) = {
+                let state = self.chain_client.chain_state_view().await?;
+                if state.tip_state.get().next_block_height != height {
+                    continue;
+                }
+                let system = &state.execution_state.system;
+                let mut balances = BTreeMap::new();
+                for owner in system.balances.indices().await? {
+                    let balance = system.balances.get(&owner).await?.unwrap_or_default();
+                    balances.insert(owner, balance);
+                }
+                (*system.balance.get(), balances)
+            };
+            let credits = bundles
+                .iter()
+                .filter(|incoming| matches!(incoming.action, MessageAction::Accept))
+                .flat_map(|incoming| &incoming.bundle.messages)
+                .filter_map(|posted| match &posted.message {
+                    Message::System(SystemMessage::Credit { target, amount, .. }) => {
+                        Some((*target, *amount))
+                    }
+                    _ => None,
+                });
+            for (owner, amount) in credits {
+                let balance = if owner == AccountOwner::CHAIN {
+                    &mut chain
+                } else {
+                    balances.entry(owner).or_insert(Amount::ZERO)
+                };
+                *balance = balance.saturating_add(amount);
+            }
+            return Ok(Balances {
+                chain: chain.to_string(),
+                accounts: balances
+                    .into_iter()
+                    .filter(|(_, balance)| *balance > Amount::ZERO)
+                    .map(|(owner, balance)| AccountBalance {
+                        owner,
+                        balance: balance.to_string(),
+                    })
+                    .collect(),
+            });
+        }
+        Err(JsError::new(
+            "The chain kept adding blocks while its balances were read",
+        ))
+    }
+
     /// Gets the identity of the default chain.
     ///
     /// # Errors
     /// If the chain couldn't be established.
     pub async fn identity(&self) -> JsResult<AccountOwner> {
-        Ok(self.chain_client.identity().await?)
+        Ok(self.proposer().identity().await?)
     }
 
     /// Adds a new owner to the default chain.
@@ -162,23 +291,35 @@ impl Chain {
         options: Option<AddOwnerOptions>,
     ) -> JsResult<()> {
         let AddOwnerOptions { weight } = options.unwrap_or_default();
-        self.client
-            .client_context
-            .lock()
-            .await
-            .apply_client_command(&self.chain_client, |_chain_client| {
-                self.chain_client.share_ownership(owner, weight)
-            })
-            .await?;
-        Ok(())
+        self.change_ownership(|ownership| {
+            ownership.owners.insert(owner, weight);
+            Ok(())
+        })
+        .await
     }
 
-    /// Gets the version information of the validators of the current network.
+    /// Retrieves an application for querying.
+    ///
+    /// # Errors
+    /// If the application ID is invalid.
+    #[wasm_bindgen]
+    pub async fn application(&self, id: &str) -> JsResult<Application> {
+        tracing::debug!("connecting to Linera application {id}");
+        Ok(Application {
+            client: self.client.clone(),
+            chain_client: self.chain_client.clone(),
+            id: id.parse()?,
+        })
+    }
+}
+
+impl Chain {
+    /// Gets the version information of the validators of the current network, by
+    /// validator.
     ///
     /// # Errors
-    /// If a validator is unreachable.
-    #[wasm_bindgen(js_name = validatorVersionInfo)]
-    pub async fn validator_version_info(&self) -> JsResult<JsValue> {
+    /// If the committee can't be read.
+    pub async fn validator_versions(&self) -> JsResult<impl serde::Serialize> {
         self.chain_client.synchronize_from_validators().await?;
         let result = self.chain_client.local_committee().await;
         let mut client = self.client.client_context.lock().await;
@@ -210,24 +351,27 @@ impl Chain {
             }
         }
 
-        Ok(validator_versions.serialize(
-            &serde_wasm_bindgen::Serializer::new()
-                .serialize_large_number_types_as_bigints(true)
-                .serialize_maps_as_objects(true),
-        )?)
+        Ok(validator_versions)
     }
 
-    /// Retrieves an application for querying.
-    ///
-    /// # Errors
-    /// If the application ID is invalid.
-    #[wasm_bindgen]
-    pub async fn application(&self, id: &str) -> JsResult<Application> {
-        web_sys::console::debug_1(&format!("connecting to Linera application {id}").into());
-        Ok(Application {
-            client: self.client.clone(),
-            chain_client: self.chain_client.clone(),
-            id: id.parse()?,
-        })
+    /// Returns the client proposing the blocks of the chain, signing as the owner the
+    /// wallet records for it rather than the one this handle was created with.
+    pub(crate) fn proposer(&self) -> ChainClient<Environment> {
+        self.client.with_wallet_owner(&self.chain_client)
+    }
+
+    /// Submits `operations` to the approval hook, then proposes a block with them.
+    pub(crate) async fn submit(&self, operations: Vec<Operation>) -> JsResult<()> {
+        let mut chain_client = self.proposer();
+        let _approval = approval::approve(&self.client, &mut chain_client, &operations).await?;
+        self.client
+            .client_context
+            .lock()
+            .await
+            .apply_client_command(&chain_client, |_chain_client| {
+                chain_client.execute_operations(operations.clone(), vec![])
+            })
+            .await?;
+        Ok(())
     }
 }
@@ -0,0 +1,261 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Management of the owners of a chain and of its round configuration.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use linera_base::{
+    crypto::Signer as _, identifiers::AccountOwner, ownership::ChainOwnership, time::TimeDelta,
+};
+use linera_client::chain_listener::ListenerCommand;
+use linera_execution::{system::SystemOperation, Operation};
+use serde::{Deserialize, Deserializer, Serialize};
+use wasm_bindgen::prelude::*;
+
+use super::Chain;
+use crate::{JsError, JsResult};
+
+/// An owner of a chain and its weight in leader election.
+#[derive(Serialize, tsify::Tsify)]
+pub struct WeightedOwner {
+    pub owner: AccountOwner,
+    pub weight: u64,
+}
+
+/// The owners of a chain and how its rounds are configured.
+#[derive(Serialize, tsify::Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnershipInfo {
+    /// Owners that may propose in the fast round.
+    pub super_owners: Vec<AccountOwner>,
+    /// Regular owners, with their weights.
+    pub owners: Vec<WeightedOwner>,
+    /// The number of rounds in which every owner may propose.
+    pub multi_leader_rounds: u32,
+    /// Whether multi-leader rounds are open to anyone rather than only to owners.
+    pub open_multi_leader_rounds: bool,
+    /// The duration of the fast round in milliseconds, if there is one.
+    pub fast_round_ms: Option<u64>,
+    /// The timeout of the first single-leader round, in milliseconds.
+    pub base_timeout_ms: u64,
+    /// How much longer each following round lasts, in milliseconds.
+    pub timeout_increment_ms: u64,
+    /// How long the chain may go without a block before the fallback owners take
+    /// over, in milliseconds.
+    pub fallback_duration_ms: u64,
+}
+
+/// Changes to the round configuration of a chain. Omitted fields are left as they
+/// are; `fastRoundMs: null` removes the fast round.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundOptions {
+    #[serde(default)]
+    pub multi_leader_rounds: Option<u32>,
+    #[serde(default)]
+    pub open_multi_leader_rounds: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    #[tsify(type = "number | null")]
+    pub fast_round_ms: Option<Option<u64>>,
+    #[serde(default)]
+    pub base_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub timeout_increment_ms: Option<u64>,
+    #[serde(default)]
+    pub fallback_duration_ms: Option<u64>,
+}
+
+/// Deserializes a field that is present, possibly as `null`, as `Some`.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
+fn millis(delta: TimeDelta) -> u64 {
+    u64::try_from(delta.as_duration().as_millis()).unwrap_or(u64::MAX)
+}
+
+impl From<ChainOwnership> for OwnershipInfo {
+    fn from(ownership: ChainOwnership) -> Self {
+        let timeouts = ownership.timeout_config;
+        Self {
+            super_owners: ownership.super_owners.into_iter().collect(),
+            owners: ownership
+                .owners
+                .into_iter()
+                .map(|(owner, weight)| WeightedOwner { owner, weight })
+                .collect(),
+            multi_leader_rounds: ownership.multi_leader_rounds,
+            open_multi_leader_rounds: ownership.open_multi_leader_rounds,
+            fast_round_ms: timeouts.fast_round_duration.map(millis),
+            base_timeout_ms: millis(timeouts.base_timeout),
+            timeout_increment_ms: millis(timeouts.timeout_increment),
+            fallback_duration_ms: millis(timeouts.fallback_duration),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Chain {
+    /// Gets the owners and round configuration of the chain.
+    ///
+    /// # Errors
+    /// If the chain state can't be read.
+    pub async fn ownership(&self) -> JsResult<OwnershipInfo> {
+        Ok(self.chain_client.query_chain_ownership().await?.into())
+    }
+
+    /// Removes an owner from the chain. The key the wallet signs with can't be
+    /// removed this way: use `rotateKey` to replace it.
+    ///
+    /// # Errors
+    /// If `owner` isn't an owner, is the wallet's own key or is the last owner, or if
+    /// the block can't be submitted.
+    #[wasm_bindgen(js_name = removeOwner)]
+    pub async fn remove_owner(&self, owner: AccountOwner) -> JsResult<()> {
+        if owner == self.proposer().identity().await? {
+            return Err(JsError::new(
+                "The wallet's own key can't be removed: rotate it instead",
+            ));
+        }
+        self.change_ownership(|ownership| {
+            if !ownership.super_owners.remove(&owner) && ownership.owners.remove(&owner).is_none() {
+                return Err(JsError::new(&format!(
+                    "{owner} is not an owner of the chain"
+                )));
+            }
+            if ownership.super_owners.is_empty() && ownership.owners.is_empty() {
+                return Err(JsError::new("The last owner of a chain can't be removed"));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Sets the weight of a regular owner in leader election.
+    ///
+    /// # Errors
+    /// If `owner` isn't a regular owner, or if the block can't be submitted.
+    #[wasm_bindgen(js_name = setOwnerWeight)]
+    pub async fn set_owner_weight(&self, owner: AccountOwner, weight: u64) -> JsResult<()> {
+        self.change_ownership(|ownership| {
+            let current = ownership.owners.get_mut(&owner).ok_or_else(|| {
+                JsError::new(&format!("{owner} is not a regular owner of the chain"))
+            })?;
+            *current = weight;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Replaces the key the wallet signs with by `new_owner`, which takes over its
+    /// place and weight among the owners, and records it in the wallet.
+    ///
+    /// The signer must already hold the key of `new_owner`.
+    ///
+    /// # Errors
+    /// If the signer doesn't hold the new key, if the current key isn't an owner, if
+    /// the block can't be submitted or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = rotateKey)]
+    pub async fn rotate_key(&self, new_owner: AccountOwner) -> JsResult<()> {
+        let old_owner = self.proposer().identity().await?;
+        if new_owner == old_owner {
+            return Err(JsError::new("The new key is the current one"));
+        }
+        if !self.chain_client.signer().contains_key(&new_owner).await? {
+            return Err(JsError::new(&format!(
+                "The signer has no key for {new_owner}"
+            )));
+        }
+        self.change_ownership(|ownership| {
+            if ownership.super_owners.remove(&old_owner) {
+                ownership.super_owners.insert(new_owner);
+            } else if let Some(weight) = ownership.owners.remove(&old_owner) {
+                ownership.owners.insert(new_owner, weight);
+            } else {
+                return Err(JsError::new(&format!(
+                    "The current key {old_owner} is not an owner of the chain"
+                )));
+            }
+            Ok(())
+        })
+        .await?;
+
+        // Every handle on the chain proposes as the owner recorded in the wallet.
+        let chain_id = self.chain_client.chain_id();
+        self.client
+            .persistent
+            .wallet
+            .chains
+            .mutate(chain_id, |chain| chain.owner = Some(new_owner));
+        // The background listener keeps the owner it started with: restart it. This
+        // only fails if it has already stopped.
+        let _ = self
+            .client
+            .listener_commands
+            .send(ListenerCommand::StopListening(BTreeSet::from([chain_id])));
+        let _ = self
+            .client
+            .listener_commands
+            .send(ListenerCommand::Listen(BTreeMap::from([(
+                chain_id,
+                Some(new_owner),
+            )])));
+        self.client.persistent.save_to_storage(false).await
+    }
+
+    /// Changes the multi-leader rounds and timeouts of the chain.
+    ///
+    /// # Errors
+    /// If the block can't be submitted.
+    #[wasm_bindgen(js_name = setRoundOptions)]
+    pub async fn set_round_options(&self, options: RoundOptions) -> JsResult<()> {
+        self.change_ownership(|ownership| {
+            if let Some(rounds) = options.multi_leader_rounds {
+                ownership.multi_leader_rounds = rounds;
+            }
+            if let Some(open) = options.open_multi_leader_rounds {
+                ownership.open_multi_leader_rounds = open;
+            }
+            let timeouts = &mut ownership.timeout_config;
+            if let Some(fast_round_ms) = options.fast_round_ms {
+                timeouts.fast_round_duration = fast_round_ms.map(TimeDelta::from_millis);
+            }
+            if let Some(ms) = options.base_timeout_ms {
+                timeouts.base_timeout = TimeDelta::from_millis(ms);
+            }
+            if let Some(ms) = options.timeout_increment_ms {
+                timeouts.timeout_increment = TimeDelta::from_millis(ms);
+            }
+            if let Some(ms) = options.fallback_duration_ms {
+                timeouts.fallback_duration = TimeDelta::from_millis(ms);
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl Chain {
+    /// Reads the ownership of the chain, lets `change` modify it, and submits a block
+    /// changing the ownership to the result, subject to approval.
+    pub(super) async fn change_ownership(
+        &self,
+        change: impl FnOnce(&mut ChainOwnership) -> JsResult<()>,
+    ) -> JsResult<()> {
+        let mut ownership = self.chain_client.query_chain_ownership().await?;
+        change(&mut ownership)?;
+        self.submit(vec![Operation::system(SystemOperation::ChangeOwnership {
+            super_owners: ownership.super_owners.into_iter().collect(),
+            owners: ownership.owners.into_iter().collect(),
+            multi_leader_rounds: ownership.multi_leader_rounds,
+            open_multi_leader_rounds: ownership.open_multi_leader_rounds,
+            timeout_config: ownership.timeout_config,
+        })])
+        .await
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Password-based encryption of the fields persisted by the wallet.
+//!
+//! A 256-bit key is derived from the user's password with Argon2id, using a random salt
+//! and cost parameters that are stored next to the wallet in a [`KdfParams`] record.
+//! Every field is then sealed with AES-256-GCM under a fresh random nonce, with the
+//! field name bound as associated data so that ciphertexts can't be swapped between
+//! fields.
+
+use std::fmt::Display;
+
+use aes_gcm::{
+    aead::{Aead as _, KeyInit as _, Payload},
+    Aes256Gcm, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// The name of the field holding the (unencrypted) [`KdfParams`] of an encrypted store.
+pub const KDF_FIELD: &str = "kdf";
+
+const KDF_ALGORITHM: &str = "argon2id";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+/// Known plaintext sealed into the [`KdfParams`] record to check a password without
+/// touching the wallet fields.
+const CHECK_PLAINTEXT: &[u8] = b"linera-wallet";
+const CHECK_FIELD: &str = "kdf:check";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Random,
+    UnsupportedKdf(String),
+    KeyDerivation(String),
+    MalformedParams,
+    MalformedCiphertext,
+    /// The password is wrong, or the data was tampered with.
+    Decryption,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Random => write!(f, "Failed to gather randomness"),
+            Error::UnsupportedKdf(algorithm) => {
+                write!(f, "Unsupported key derivation function: {algorithm}")
+            }
+            Error::KeyDerivation(e) => write!(f, "Failed to derive the wallet key: {e}"),
+            Error::MalformedParams => write!(f, "Malformed key derivation parameters"),
+            Error::MalformedCiphertext => write!(f, "Malformed encrypted field"),
+            Error::Decryption => write!(
+                f,
+                "Failed to decrypt the wallet: incorrect password or corrupted data"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The parameters used to derive a [`WalletKey`] from a password.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    /// Memory cost, in KiB.
+    pub m_cost: u32,
+    /// Number of iterations.
+    pub t_cost: u32,
+    /// Degree of parallelism.
+    pub p_cost: u32,
+    /// Hex-encoded random salt.
+    pub salt: String,
+    /// Hex-encoded [`CHECK_PLAINTEXT`] sealed under the derived key.
+    pub check: String,
+}
+
+impl KdfParams {
+    /// Generates fresh parameters with a random salt, and derives the key for `password`.
+    ///
+    /// # Errors
+    /// If randomness is unavailable or the key can't be derived.
+    pub fn generate(password: &str) -> Result<(Self, WalletKey), Error> {
+        // OWASP recommendation for Argon2id: 19 MiB of memory, two iterations.
+        let mut params = Self {
+            algorithm: KDF_ALGORITHM.to_owned(),
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+            salt: hex::encode(random_bytes::<SALT_LEN>()?),
+            check: String::new(),
+        };
+        let key = params.derive(password)?;
+        params.check = hex::encode(key.seal(CHECK_FIELD, CHECK_PLAINTEXT)?);
+        Ok((params, key))
+    }
+
+    /// Derives the key for `password`, checking it against the stored check value.
+    ///
+    /// # Errors
+    /// If the password is incorrect or the parameters are malformed.
+    pub fn unlock(&self, password: &str) -> Result<WalletKey, Error> {
+        let key = self.derive(password)?;
+        let check = hex::decode(&self.check).map_err(|_| Error::MalformedParams)?;
+        if key.open(CHECK_FIELD, &check)?.as_slice() != CHECK_PLAINTEXT {
+            return Err(Error::Decryption);
+        }
+        Ok(key)
+    }
+
+    fn derive(&self, password: &str) -> Result<WalletKey, Error> {
+        if self.algorithm != KDF_ALGORITHM {
+            return Err(Error::UnsupportedKdf(self.algorithm.clone()));
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| Error::MalformedParams)?;
+        let params = argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| Error::KeyDerivation(e.to_string()))?;
+        let argon2 =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        argon2
+            .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| Error::KeyDerivation(e.to_string()))?;
+        Ok(WalletKey(key))
+    }
+}
+
+/// A symmetric key protecting the wallet. It is wiped from memory when dropped.
+pub struct WalletKey(Zeroizing<[u8; KEY_LEN]>);
+
+impl WalletKey {
+    /// Encrypts `plaintext` for storage under `field`, returning `nonce || ciphertext`.
+    ///
+    /// # Errors
+    /// If randomness is unavailable.
+    pub fn seal(&self, field: &str, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: field.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::MalformedCiphertext)?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts and authenticates a value produced by [`WalletKey::seal`] for `field`.
+    ///
+    /// # Errors
+    /// If the value is malformed, was sealed for another field, or under another key.
+    pub fn open(&self, field: &str, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::MalformedCiphertext);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: field.as_bytes(),
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| Error::Decryption)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(self.0.as_ref().into())
+    }
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> Result<[u8; N], Error> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|_| Error::Random)?;
+    Ok(bytes)
+}
@@ -0,0 +1,136 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The error type returned by the client API.
+//!
+//! In the browser errors surface as a JS `Error`. Native builds can't create JS
+//! values, so they use an error carrying just the message. Both are converted from
+//! any [`std::error::Error`] in the same way.
+//!
+//! Errors caused by a signer keep the [`signer::Error`]: in the browser the JS
+//! `Error` is named `SignerError` and has a `kind` property, along with `jsName` and
+//! `jsMessage` properties if the signer threw the error from JS, so that callers can
+//! tell a user rejecting a request apart from a failure.
+
+use crate::signer;
+
+#[cfg(feature = "web")]
+pub use web::JsError;
+
+#[cfg(not(feature = "web"))]
+pub use native::JsError;
+
+#[cfg(feature = "web")]
+mod web {
+    use wasm_bindgen::{JsCast as _, JsValue};
+
+    use super::signer;
+
+    /// An error surfacing as a JS `Error`.
+    #[derive(Debug)]
+    pub struct JsError {
+        value: JsValue,
+    }
+
+    impl JsError {
+        #[must_use]
+        pub fn new(message: &str) -> Self {
+            Self {
+                value: js_sys::Error::new(message).into(),
+            }
+        }
+
+        /// The message of the error.
+        #[must_use]
+        pub fn message(&self) -> String {
+            match self.value.dyn_ref::<js_sys::Error>() {
+                Some(error) => error.message().into(),
+                None => format!("{:?}", self.value),
+            }
+        }
+
+        fn from_signer_error(message: &str, error: &signer::Error) -> Self {
+            let js_error = js_sys::Error::new(message);
+            js_error.set_name("SignerError");
+            let set = |key: &str, value: &str| {
+                // Setting a property of a fresh `Error` can't fail.
+                let _ = js_sys::Reflect::set(&js_error, &key.into(), &value.into());
+            };
+            set("kind", error.kind());
+            if let Some((name, message)) = error.js_error() {
+                set("jsName", name);
+                set("jsMessage", message);
+            }
+            Self {
+                value: js_error.into(),
+            }
+        }
+    }
+
+    impl<E: std::error::Error + 'static> From<E> for JsError {
+        fn from(error: E) -> Self {
+            let message = error.to_string();
+            match signer::Error::find(&error) {
+                Some(signer_error) => Self::from_signer_error(&message, signer_error),
+                None => Self::new(&message),
+            }
+        }
+    }
+
+    impl From<JsError> for JsValue {
+        fn from(error: JsError) -> Self {
+            error.value
+        }
+    }
+}
+
+#[cfg(not(feature = "web"))]
+mod native {
+    use std::fmt;
+
+    use super::signer;
+
+    /// An error message, standing in for JS errors in native builds.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct JsError {
+        message: String,
+        signer_error: Option<signer::Error>,
+    }
+
+    impl JsError {
+        #[must_use]
+        pub fn new(message: &str) -> Self {
+            Self {
+                message: message.to_string(),
+                signer_error: None,
+            }
+        }
+
+        /// The message of the error.
+        #[must_use]
+        pub fn message(&self) -> String {
+            self.message.clone()
+        }
+
+        /// The signer error that caused this error, if any.
+        #[must_use]
+        pub fn signer_error(&self) -> Option<&signer::Error> {
+            self.signer_error.as_ref()
+        }
+    }
+
+    impl fmt::Display for JsError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.message)
+        }
+    }
+
+    impl<E: std::error::Error + 'static> From<E> for JsError {
+        fn from(error: E) -> Self {
+            Self {
+                message: error.to_string(),
+                signer_error: signer::Error::find(&error).cloned(),
+            }
+        }
+    }
+}
@@ -4,14 +4,10 @@
 use linera_base::identifiers::AccountOwner;
 use linera_core::wallet;
 use wasm_bindgen::prelude::*;
-use web_sys::wasm_bindgen;
 
-use crate::{
-    utils::IndexedDbStorage,
-    wallet::{PersistentWallet, Wallet},
-};
+use crate::wallet::{schema, CreatedVia, PersistentWallet, Wallet};
 
-use super::JsResult;
+use super::{JsError, JsResult};
 
 #[wasm_bindgen]
 pub struct Faucet(linera_faucet_client::Faucet);
@@ -24,21 +20,32 @@ impl Faucet {
         Faucet(linera_faucet_client::Faucet::new(url))
     }
 
-    /// Creates a new wallet from the faucet.
+    /// Creates a new wallet from the faucet, in the selected profile.
+    ///
+    /// If `password` is provided, the wallet is encrypted at rest under it.
     ///
     /// # Errors
-    /// If we couldn't retrieve the genesis config from the faucet.
+    /// If we couldn't retrieve the genesis config from the faucet, or if
+    /// this would overwrite a password-protected wallet without a password.
     #[wasm_bindgen(js_name = createWallet)]
-    pub async fn create_wallet(&self) -> JsResult<PersistentWallet> {
-        let storage = IndexedDbStorage::new("linera", "ldb", 2u32);
+    pub async fn create_wallet(&self, password: Option<String>) -> JsResult<PersistentWallet> {
+        let profile = crate::profile::current().await?;
+        let storage = schema::storage_for(&profile);
 
-        let wallet = Wallet {
-            chains: std::rc::Rc::new(wallet::Memory::default()),
-            default: None,
-            genesis_config: self.0.genesis_config().await?,
-        };
+        let wallet = Wallet::empty(self.0.genesis_config().await?);
 
-        let p = PersistentWallet::new(wallet, storage);
+        let mut p = PersistentWallet::new(wallet, std::rc::Rc::new(storage), profile);
+        if let Some(password) = password {
+            // Overwriting a password-protected wallet requires unlocking it first.
+            // Writes the whole wallet, genesis config included.
+            p.set_password(password).await?;
+            return Ok(p);
+        }
+        if crate::session::is_protected_for(&p.profile).await? {
+            return Err(JsError::new(
+                "A password-protected wallet already exists: a password is required",
+            ));
+        }
         p.save_to_storage(true).await.map_err(|e| {
             tracing::error!("save_to_storage failed: {:?}", e);
             e
@@ -53,6 +60,7 @@ impl Faucet {
     /// - if we fail to get the list of current validators from the faucet
     /// - if we fail to claim the chain from the faucet
     /// - if we fail to persist the new chain or keypair to the wallet
+    /// - if the wallet is locked
     ///
     /// # Panics
     /// If an error occurs in the chain listener task.
@@ -62,6 +70,7 @@ impl Faucet {
         w: &mut PersistentWallet,
         owner: AccountOwner,
     ) -> JsResult<String> {
+        crate::session::ensure_unlocked_for(&w.profile).await?;
         tracing::info!(
             "Requesting a new chain for owner {} using the faucet at address {}",
             owner,
@@ -76,11 +85,12 @@ impl Faucet {
                 ..description.into()
             },
         );
-        if w.wallet.default.is_none() {
-            w.wallet.default = Some(chain_id);
+        w.wallet.set_created_via(chain_id, CreatedVia::Faucet);
+        if w.wallet.default.get().is_none() {
+            w.wallet.set_default(Some(chain_id));
         }
 
-        let _ = w.save_to_storage(false).await;
+        w.save_to_storage(false).await?;
         Ok(chain_id.to_string())
     }
 }
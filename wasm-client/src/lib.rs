@@ -13,6 +13,10 @@ The `signer` subdirectory contains a TypeScript interface specifying the types o
 that can be passed as signers — cryptographic integrations used to sign transactions, as
 well as a demo implementation (not recommended for production use) that stores a private
 key directly in memory and uses it to sign.
+
+The browser integration (IndexedDB, the JS event loop and console logging) lives behind
+the default `web` feature. Without it the library builds natively for headless use, with
+wallets stored in JSON files, chain state kept in memory, and an in-memory signer.
 */
 
 // We sometimes need functions in this module to be async in order to
@@ -20,32 +24,76 @@ key directly in memory and uses it to sign.
 #![allow(clippy::unused_async)]
 #![recursion_limit = "256"]
 
-use std::{rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+    sync::Arc,
+};
 
 use futures::{future::FutureExt as _, lock::Mutex as AsyncMutex};
 use linera_base::identifiers::{AccountOwner, ChainId};
-use linera_client::chain_listener::{ChainListener, ClientContext as _};
-use linera_core::JoinSetExt;
+use linera_client::chain_listener::{ChainListener, ClientContext as _, ListenerCommand};
+use linera_core::{
+    client::{AbortOnDrop, BlanketMessagePolicy, ChainClient, MessagePolicy},
+    JoinSetExt,
+};
 use wallet::PersistentWallet;
 use wasm_bindgen::prelude::*;
-use web_sys::wasm_bindgen;
 
 pub mod chain;
+pub mod crypto;
+pub mod error;
+pub mod runtime;
 pub mod utils;
 pub use chain::Chain;
 pub mod faucet;
+pub mod profile;
+pub mod session;
 
 pub mod signer;
+#[cfg(not(feature = "web"))]
+pub use linera_base::crypto::InMemorySigner as Signer;
+#[cfg(feature = "web")]
 pub use signer::Signer;
 pub mod storage;
 pub use storage::Storage;
 pub mod wallet;
+#[cfg(feature = "web")]
+mod web;
+
+pub use error::JsError;
+
+/// The chains of a wallet, shared between the wallet and its client.
+#[cfg(feature = "web")]
+pub type WalletChains = Rc<linera_core::wallet::Memory>;
+/// The chains of a wallet, shared between the wallet and its client.
+#[cfg(not(feature = "web"))]
+pub type WalletChains = Arc<linera_core::wallet::Memory>;
 
 pub type Network = linera_rpc::node_provider::NodeProvider;
-pub type Environment =
-    linera_core::environment::Impl<Storage, Network, Signer, Rc<linera_core::wallet::Memory>>;
+pub type Environment = linera_core::environment::Impl<
+    Storage,
+    Network,
+    chain::approval::ApprovingSigner,
+    WalletChains,
+>;
 type JsResult<T> = Result<T, JsError>;
 
+/// Options of a `Client`: those of the Linera client, along with the following.
+#[derive(Default, serde::Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientOptions {
+    #[serde(flatten)]
+    pub linera: linera_client::Options,
+    /// Whether to receive the incoming messages of the chains as soon as they arrive.
+    /// Defaults to `true`; if `false`, they wait in the inboxes until
+    /// `Chain.processInbox` or `Chain.rejectMessages` is called.
+    #[serde(default)]
+    pub auto_process_inbox: Option<bool>,
+}
+
 /// The full client API, exposed to the wallet implementation. Calls
 /// to this API can be trusted to have originated from the user's
 /// request.
@@ -59,49 +107,148 @@ pub struct Client {
     // hard-coded by `ChainListener`.
     client_context: Arc<AsyncMutex<linera_client::ClientContext<Environment>>>,
     persistent: PersistentWallet,
+    /// Sends commands to the background chain listener.
+    listener_commands: tokio::sync::mpsc::UnboundedSender<ListenerCommand>,
+    /// The listeners of the chains assigned with `assignChain`, which stop when
+    /// dropped.
+    assigned_listeners: Rc<RefCell<HashMap<ChainId, AbortOnDrop>>>,
+    /// The function approving blocks before they are signed, if any, shared with the
+    /// signer.
+    approvals: chain::approval::Approvals,
+    /// Whether the chain listener receives the incoming messages of the chains.
+    auto_process_inbox: bool,
 }
 
+#[cfg(feature = "web")]
 #[wasm_bindgen]
 impl Client {
-    /// Creates a new client and connects to the network.
+    /// Creates a new client for the selected profile and connects to the network,
+    /// signing with a JS `Signer`.
     ///
     /// # Errors
     /// On transport or protocol error, if persistent storage is
-    /// unavailable, or if `options` is incorrectly structured.
+    /// unavailable, if `options` is incorrectly structured, if the
+    /// wallet is locked, or if it belongs to another profile.
     #[wasm_bindgen(constructor)]
     pub async fn new(
         w: &PersistentWallet,
-        signer: Signer,
-        options: Option<linera_client::Options>,
+        signer: signer::JsSigner,
+        options: Option<ClientOptions>,
     ) -> Result<Client, JsError> {
-        let options = options.unwrap_or_default();
+        Self::connect(w, Signer::Js(signer), options).await
+    }
 
-        let mut storage = storage::get_storage().await?;
+    /// Like the constructor, but signs with keys held in wasm memory.
+    ///
+    /// # Errors
+    /// As for the constructor.
+    #[wasm_bindgen(js_name = "withInMemorySigner")]
+    pub async fn with_in_memory_signer(
+        w: &PersistentWallet,
+        signer: &signer::InMemorySigner,
+        options: Option<ClientOptions>,
+    ) -> Result<Client, JsError> {
+        Self::connect(w, Signer::InMemory(signer.clone()), options).await
+    }
+
+    /// Like the constructor, but signs with a composite signer, which routes each
+    /// owner to one of its signers. Signers can be added to it while the client runs.
+    ///
+    /// # Errors
+    /// As for the constructor.
+    #[wasm_bindgen(js_name = "withCompositeSigner")]
+    pub async fn with_composite_signer(
+        w: &PersistentWallet,
+        signer: &signer::CompositeSigner,
+        options: Option<ClientOptions>,
+    ) -> Result<Client, JsError> {
+        Self::connect(w, Signer::Composite(signer.clone()), options).await
+    }
+
+    /// Like the constructor, but forwards signing requests to another context over
+    /// the `MessagePort` of a remote signer.
+    ///
+    /// # Errors
+    /// As for the constructor.
+    #[wasm_bindgen(js_name = "withRemoteSigner")]
+    pub async fn with_remote_signer(
+        w: &PersistentWallet,
+        signer: &signer::RemoteSigner,
+        options: Option<ClientOptions>,
+    ) -> Result<Client, JsError> {
+        Self::connect(w, Signer::Remote(signer.clone()), options).await
+    }
+}
+
+impl Client {
+    /// Creates a new client for the selected profile and connects to the network.
+    ///
+    /// # Errors
+    /// On transport or protocol error, if persistent storage is
+    /// unavailable, if `options` is incorrectly structured, if the
+    /// wallet is locked, or if it belongs to another profile.
+    pub async fn connect(
+        w: &PersistentWallet,
+        signer: Signer,
+        options: Option<ClientOptions>,
+    ) -> Result<Client, JsError> {
+        session::ensure_unlocked_for(&w.profile).await?;
+        let selected = profile::current().await?;
+        if w.profile.id != selected.id {
+            return Err(JsError::new(&format!(
+                "The wallet belongs to profile {:?}, but profile {:?} is selected",
+                w.profile.name, selected.name
+            )));
+        }
+        let ClientOptions {
+            linera: mut options,
+            auto_process_inbox,
+        } = options.unwrap_or_default();
+        let auto_process_inbox = auto_process_inbox.unwrap_or(true);
+        options.chain_listener_config.skip_process_inbox = !auto_process_inbox;
+
+        let mut storage = storage::get_storage(&w.profile).await?;
         w.wallet
             .genesis_config
             .initialize_storage(&mut storage)
             .await?;
 
+        let approvals = chain::approval::Approvals::default();
         let client = linera_client::ClientContext::new(
             storage.clone(),
             w.wallet.chains.clone(),
-            signer,
+            chain::approval::ApprovingSigner::new(signer, approvals.clone()),
             &options,
-            w.wallet.default,
+            w.wallet.default.get(),
             w.wallet.genesis_config.clone(),
         )
         .await?;
 
         // The `Arc` here is useless, but it is required by the `ChainListener` API.
-        #[expect(clippy::arc_with_non_send_sync)]
+        #[cfg_attr(feature = "web", expect(clippy::arc_with_non_send_sync))]
         let client = Arc::new(AsyncMutex::new(client));
+
+        // The client context keeps its own copy of the default chain: update it
+        // whenever the wallet's changes.
+        let context = Arc::downgrade(&client);
+        let default = Rc::downgrade(&w.wallet.default);
+        w.wallet.on_default_change(move || {
+            let (Some(context), Some(default)) = (context.upgrade(), default.upgrade()) else {
+                return;
+            };
+            runtime::spawn_local(async move {
+                context.lock().await.default_chain = default.get();
+            });
+        });
+
         let client_clone = client.clone();
+        let (listener_commands, listener_command_receiver) = tokio::sync::mpsc::unbounded_channel();
         let chain_listener = ChainListener::new(
             options.chain_listener_config,
             client_clone,
             storage,
             tokio_util::sync::CancellationToken::new(),
-            tokio::sync::mpsc::unbounded_channel().1,
+            listener_command_receiver,
             true,
         )
         .run() // Enable background sync
@@ -109,7 +256,7 @@ impl Client {
         .await?
         .boxed_local();
 
-        wasm_bindgen_futures::spawn_local(
+        runtime::spawn_local(
             async move {
                 if let Err(error) = chain_listener.await {
                     tracing::error!("ChainListener error: {error:?}");
@@ -122,12 +269,61 @@ impl Client {
         Ok(Client {
             client_context: client,
             persistent: w.clone(),
+            listener_commands,
+            assigned_listeners: Rc::default(),
+            approvals,
+            auto_process_inbox,
         })
     }
 
-    /// Assigns a new chain and returns the ChainClient for use
+    /// Returns a copy of `chain_client` proposing as the owner the wallet records for
+    /// its chain, which another handle on the chain may have changed with `rotateKey`.
+    pub(crate) fn with_wallet_owner(
+        &self,
+        chain_client: &ChainClient<Environment>,
+    ) -> ChainClient<Environment> {
+        let mut chain_client = chain_client.clone();
+        if let Some(owner) = self.persistent.wallet.owner(chain_client.chain_id()) {
+            chain_client.set_preferred_owner(owner);
+        }
+        chain_client
+    }
+
+    /// Creates the client of `chain_id`. Unless the inbox is processed automatically,
+    /// its blocks leave the incoming messages alone, for `processInbox` to receive.
+    async fn make_chain_client(
+        &self,
+        ctx: &linera_client::ClientContext<Environment>,
+        chain_id: ChainId,
+    ) -> JsResult<ChainClient<Environment>> {
+        let mut chain_client = ctx.make_chain_client(chain_id).await?;
+        if !self.auto_process_inbox {
+            chain_client.options_mut().message_policy =
+                MessagePolicy::new(BlanketMessagePolicy::Ignore, None);
+        }
+        Ok(chain_client)
+    }
+
+    /// Sets the function approving blocks before they are signed, or removes it.
+    ///
+    /// If the hook doesn't approve a block, the call proposing it fails with a
+    /// `SignerError` of kind `userRejected`.
+    pub fn set_approval_hook(&self, hook: Option<chain::approval::ApprovalHook>) {
+        self.approvals.set_hook(hook);
+    }
+}
+
+#[wasm_bindgen]
+impl Client {
+    /// Assigns a new chain, given by chain ID or by its label in the wallet, and
+    /// returns the ChainClient for use
+    ///
+    /// # Errors
+    /// If the chain label is unknown, or if the chain can't be assigned or
+    /// synchronized.
     #[wasm_bindgen(js_name = "assignChain")]
-    pub async fn assign_and_use(&self, chain_id: ChainId, owner: AccountOwner) -> JsResult<Chain> {
+    pub async fn assign_and_use(&self, chain: String, owner: AccountOwner) -> JsResult<Chain> {
+        let chain_id = self.persistent.wallet.resolve(&chain)?;
         let mut ctx = self.client_context.lock().await;
         ctx.assign_new_chain_to_key(chain_id, owner).await?;
 
@@ -135,13 +331,19 @@ impl Client {
             ctx.assign_new_chain_to_key(chain_id, owner).await?;
         }
 
-        let chain_client = ctx.make_chain_client(chain_id).await?;
+        let chain_client = self.make_chain_client(&ctx, chain_id).await?;
 
-        let (listener, _listnen_handle, _) = chain_client.listen().await?;
+        let (listener, listen_handle, _) = chain_client.listen().await?;
 
         ctx.chain_listeners.spawn_task(listener);
+        self.assigned_listeners
+            .borrow_mut()
+            .insert(chain_id, listen_handle);
         chain_client.synchronize_from_validators().await?;
 
+        self.persistent
+            .wallet
+            .set_created_via(chain_id, wallet::CreatedVia::Assigned);
         self.persistent.save_to_storage(false).await?;
 
         drop(ctx);
@@ -152,16 +354,26 @@ impl Client {
         })
     }
 
-    /// Connect to a chain on the Linera network.
+    /// Connect to a chain on the Linera network, given by chain ID or by its label in
+    /// the wallet.
     /// If no chain is provided, Default chain is used
     /// # Errors
     ///
-    /// If the wallet could not be read or chain synchronization fails.
+    /// If the chain label is unknown, the wallet could not be read or chain
+    /// synchronization fails.
     #[wasm_bindgen]
-    pub async fn chain(&self, chain: Option<ChainId>) -> JsResult<Chain> {
+    pub async fn chain(&self, chain: Option<String>) -> JsResult<Chain> {
+        let chain_id = match chain {
+            Some(chain) => self.persistent.wallet.resolve(&chain)?,
+            None => self
+                .persistent
+                .wallet
+                .default
+                .get()
+                .ok_or_else(|| JsError::new("The wallet has no default chain"))?,
+        };
         let mut ctx = self.client_context.lock().await;
-        let chain_id = chain.unwrap_or_else(|| ctx.default_chain());
-        let chain_client = ctx.make_chain_client(chain_id).await?;
+        let chain_client = self.make_chain_client(&ctx, chain_id).await?;
 
         chain_client.synchronize_from_validators().await?;
         ctx.update_wallet(&chain_client).await?;
@@ -175,8 +387,30 @@ impl Client {
         };
         Ok(chain)
     }
+
+    /// Stops following `chain`, given by chain ID or label: its listener is stopped,
+    /// and it is removed from the wallet along with its metadata. If it was the default
+    /// chain, there is no default chain afterwards.
+    ///
+    /// The chain's state stays in the chain storage cache until it is cleared.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet, or if the wallet can't be saved.
+    #[wasm_bindgen]
+    pub async fn unassign(&self, chain: String) -> JsResult<()> {
+        let chain_id = self.persistent.wallet.resolve_owned(&chain)?;
+        // Dropping the handle stops the listener started by `assignChain`, if any.
+        self.assigned_listeners.borrow_mut().remove(&chain_id);
+        // This only fails if the background listener has already stopped.
+        let _ = self
+            .listener_commands
+            .send(ListenerCommand::StopListening(BTreeSet::from([chain_id])));
+        self.persistent.wallet.forget(chain_id);
+        self.persistent.save_to_storage(false).await
+    }
 }
 
+#[cfg(feature = "web")]
 #[wasm_bindgen(start)]
 pub fn main() {
     use tracing_subscriber::{
@@ -0,0 +1,340 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Named wallet profiles.
+//!
+//! Each profile has its own wallet, Secret vault and chain state, stored in IndexedDB
+//! databases named after its ID. Profiles are listed in a registry database along
+//! with the selected one, which every entry point opens. The `default` profile uses
+//! the databases from before profiles existed, so an existing wallet becomes the
+//! default profile.
+//!
+//! The ID of a profile never changes, so that renaming it doesn't touch its storage.
+
+use std::cell::RefCell;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    session, storage,
+    utils::{self, PlatformStorage, SecretVault, WalletStorage as _},
+    wallet::schema,
+    JsError, JsResult,
+};
+
+const REGISTRY_DB_NAME: &str = "linera_profiles";
+const REGISTRY_STORE_NAME: &str = "profiles";
+const REGISTRY_DB_VERSION: u32 = 1;
+const PROFILES_FIELD: &str = "profiles";
+const SELECTED_FIELD: &str = "selected";
+const NEXT_ID_FIELD: &str = "next_id";
+
+const DEFAULT_ID: &str = "default";
+const MAX_NAME_LEN: usize = 64;
+
+thread_local! {
+    static SELECTED: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// A wallet profile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// The stable identifier the storage is named after.
+    pub id: String,
+    /// The name shown to the user.
+    pub name: String,
+}
+
+impl Profile {
+    fn default_profile() -> Self {
+        Self {
+            id: DEFAULT_ID.to_string(),
+            name: DEFAULT_ID.to_string(),
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        self.id == DEFAULT_ID
+    }
+
+    /// The IndexedDB database holding the wallet of this profile.
+    pub fn wallet_db(&self) -> String {
+        if self.is_default() {
+            schema::DB_NAME.to_string()
+        } else {
+            format!("{}_{}", schema::DB_NAME, self.id)
+        }
+    }
+
+    /// The IndexedDB database that held the Secret vault of this profile before it
+    /// moved into the wallet database.
+    pub fn vault_db(&self) -> String {
+        if self.is_default() {
+            "linera_store".to_string()
+        } else {
+            format!("linera_store_{}", self.id)
+        }
+    }
+
+    /// The namespace of the chain state of this profile.
+    pub fn chain_state_namespace(&self) -> String {
+        if self.is_default() {
+            storage::NAMESPACE.to_string()
+        } else {
+            format!("{}_{}", storage::NAMESPACE, self.id)
+        }
+    }
+}
+
+/// Returns the selected profile, reading the registry the first time we are asked.
+///
+/// # Errors
+/// If the registry is inaccessible or malformed.
+pub async fn current() -> JsResult<Profile> {
+    if let Some(profile) = SELECTED.with_borrow(Clone::clone) {
+        return Ok(profile);
+    }
+    let registry = Registry::read().await?;
+    let profile = registry.selected()?.clone();
+    SELECTED.with_borrow_mut(|selected| *selected = Some(profile.clone()));
+    Ok(profile)
+}
+
+struct Registry {
+    profiles: Vec<Profile>,
+    selected: String,
+    next_id: u32,
+}
+
+impl Registry {
+    fn storage() -> PlatformStorage {
+        utils::open_store(REGISTRY_DB_NAME, REGISTRY_STORE_NAME, REGISTRY_DB_VERSION)
+    }
+
+    /// Reads the registry, which holds only the default profile until another one is
+    /// created.
+    async fn read() -> JsResult<Self> {
+        let mut fields = Self::storage()
+            .read_fields(&[PROFILES_FIELD, SELECTED_FIELD, NEXT_ID_FIELD])
+            .await?
+            .into_iter();
+        let profiles = match fields.next().flatten() {
+            Some(value) => serde_json::from_value(value)
+                .map_err(|e| JsError::new(&format!("Malformed profile registry: {e}")))?,
+            None => vec![Profile::default_profile()],
+        };
+        let selected = fields
+            .next()
+            .flatten()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_else(|| DEFAULT_ID.to_string());
+        let next_id = fields
+            .next()
+            .flatten()
+            .and_then(|value| value.as_f64())
+            .and_then(num_traits::cast)
+            .unwrap_or(1);
+        Ok(Self {
+            profiles,
+            selected,
+            next_id,
+        })
+    }
+
+    async fn write(&self) -> JsResult<()> {
+        let profiles = serde_json::to_value(&self.profiles)
+            .map_err(|e| JsError::new(&format!("Failed to serialize profiles: {e}")))?;
+        Self::storage()
+            .write_fields(vec![
+                (PROFILES_FIELD.to_string(), profiles),
+                (SELECTED_FIELD.to_string(), self.selected.clone().into()),
+                (NEXT_ID_FIELD.to_string(), self.next_id.into()),
+            ])
+            .await?;
+        Ok(())
+    }
+
+    fn selected(&self) -> JsResult<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.id == self.selected)
+            .ok_or_else(|| JsError::new("The selected profile doesn't exist"))
+    }
+
+    fn position(&self, name: &str) -> JsResult<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or_else(|| JsError::new(&format!("No profile named {name:?}")))
+    }
+
+    fn check_new_name(&self, name: &str) -> JsResult<()> {
+        if name.trim().is_empty() || name.trim() != name {
+            return Err(JsError::new(
+                "Profile names must be non-empty, without leading or trailing spaces",
+            ));
+        }
+        if name.chars().count() > MAX_NAME_LEN {
+            return Err(JsError::new(&format!(
+                "Profile names must be at most {MAX_NAME_LEN} characters long"
+            )));
+        }
+        if self.profiles.iter().any(|profile| profile.name == name) {
+            return Err(JsError::new(&format!(
+                "A profile named {name:?} already exists"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Management of wallet profiles, exposed to JS.
+///
+/// Switching profiles locks the session. Clients that are already running keep using
+/// the profile they were created with.
+#[wasm_bindgen(js_name = "Profiles")]
+pub struct JsProfiles;
+
+#[wasm_bindgen(js_class = "Profiles")]
+impl JsProfiles {
+    /// Returns the names of all profiles.
+    ///
+    /// # Errors
+    /// If the registry is inaccessible.
+    pub async fn list() -> JsResult<Vec<String>> {
+        Ok(Registry::read()
+            .await?
+            .profiles
+            .into_iter()
+            .map(|profile| profile.name)
+            .collect())
+    }
+
+    /// Returns the name of the selected profile.
+    ///
+    /// # Errors
+    /// If the registry is inaccessible.
+    pub async fn current() -> JsResult<String> {
+        Ok(current().await?.name)
+    }
+
+    /// Creates an empty profile named `name`, without selecting it.
+    ///
+    /// # Errors
+    /// If the name is invalid or taken, or if the registry is inaccessible.
+    pub async fn create(name: String) -> JsResult<()> {
+        let mut registry = Registry::read().await?;
+        registry.check_new_name(&name)?;
+        let id = format!("p{}", registry.next_id);
+        registry.next_id += 1;
+        registry.profiles.push(Profile { id, name });
+        registry.write().await
+    }
+
+    /// Selects the profile named `name`, locking the session.
+    ///
+    /// # Errors
+    /// If there is no such profile, or if the registry is inaccessible.
+    #[wasm_bindgen(js_name = "switch")]
+    pub async fn switch(name: String) -> JsResult<()> {
+        let mut registry = Registry::read().await?;
+        let profile = registry.profiles[registry.position(&name)?].clone();
+        registry.selected.clone_from(&profile.id);
+        registry.write().await?;
+        SELECTED.with_borrow_mut(|selected| *selected = Some(profile));
+        session::lock();
+        Ok(())
+    }
+
+    /// Renames the profile named `from` to `to`.
+    ///
+    /// # Errors
+    /// If there is no profile named `from`, if `to` is invalid or taken, or if the
+    /// registry is inaccessible.
+    pub async fn rename(from: String, to: String) -> JsResult<()> {
+        let mut registry = Registry::read().await?;
+        let index = registry.position(&from)?;
+        registry.check_new_name(&to)?;
+        registry.profiles[index].name.clone_from(&to);
+        registry.write().await?;
+        SELECTED.with_borrow_mut(|selected| {
+            if let Some(profile) = selected.as_mut().filter(|profile| profile.name == from) {
+                profile.name = to;
+            }
+        });
+        Ok(())
+    }
+
+    /// Deletes the profile named `name` along with its wallet, secrets and chain
+    /// state. The selected profile can't be deleted.
+    ///
+    /// # Errors
+    /// If there is no such profile, if it is selected, or if storage is inaccessible.
+    pub async fn delete(name: String) -> JsResult<()> {
+        let mut registry = Registry::read().await?;
+        let index = registry.position(&name)?;
+        let profile = registry.profiles[index].clone();
+        if profile.id == registry.selected {
+            return Err(JsError::new(
+                "The selected profile can't be deleted: switch to another one first",
+            ));
+        }
+
+        // The registry entry goes last, so that a failed deletion can be retried.
+        schema::storage_for(&profile).delete_database().await?;
+        // The vault shares the wallet database, but natively each store is a file of
+        // its own.
+        schema::vault_storage_for(&profile)
+            .delete_database()
+            .await?;
+        SecretVault::legacy_storage_for(&profile)
+            .delete_database()
+            .await?;
+        storage::clear_namespace(&profile.chain_state_namespace()).await?;
+        registry.profiles.remove(index);
+        registry.write().await
+    }
+}
+
+#[cfg(all(test, not(feature = "web")))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::wallet::FileStorage;
+
+    #[test]
+    fn deleting_a_profile_leaves_no_file() {
+        FileStorage::use_test_data_dir();
+        block_on(async {
+            JsProfiles::create("deleted".to_string()).await.unwrap();
+            let registry = Registry::read().await.unwrap();
+            let profile = registry.profiles[registry.position("deleted").unwrap()].clone();
+            schema::storage_for(&profile)
+                .write_field("chains", serde_json::json!({}))
+                .await
+                .unwrap();
+            SecretVault::open(&profile)
+                .await
+                .unwrap()
+                .write_field("vault:mnemonic", "secret".into())
+                .await
+                .unwrap();
+            SecretVault::legacy_storage_for(&profile)
+                .write_field("vault:key", "secret".into())
+                .await
+                .unwrap();
+
+            JsProfiles::delete("deleted".to_string()).await.unwrap();
+            let data_dir = FileStorage::data_dir();
+            assert!(!data_dir.join(profile.wallet_db()).exists());
+            assert!(!data_dir.join(profile.vault_db()).exists());
+            assert!(JsProfiles::list()
+                .await
+                .unwrap()
+                .iter()
+                .all(|name| name != "deleted"));
+        });
+    }
+}
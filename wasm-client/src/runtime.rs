@@ -0,0 +1,77 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Background tasks and timers.
+//!
+//! The client is single-threaded: tasks are spawned on the JS event loop in the
+//! browser, and on the current Tokio `LocalSet` in native builds.
+
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "web")]
+use wasm_bindgen::{JsCast as _, JsValue};
+
+#[cfg(feature = "web")]
+use crate::JsError;
+use crate::JsResult;
+
+/// Runs `future` in the background on the current thread.
+///
+/// # Panics
+/// In native builds, if called outside of a Tokio `LocalSet`.
+pub fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    #[cfg(feature = "web")]
+    wasm_bindgen_futures::spawn_local(future);
+    #[cfg(not(feature = "web"))]
+    drop(tokio::task::spawn_local(future));
+}
+
+/// Waits for `duration` without blocking the current thread.
+///
+/// Dropping the future before it completes clears the underlying timer.
+///
+/// # Errors
+/// In the browser, if the global scope has no `setTimeout` or `clearTimeout`.
+pub async fn sleep(duration: Duration) -> JsResult<()> {
+    #[cfg(feature = "web")]
+    {
+        let millis = i32::try_from(duration.as_millis()).unwrap_or(i32::MAX);
+        // `setTimeout` is available both in windows and in workers.
+        let set_timeout = global_function("setTimeout")?;
+        let clear_timeout = global_function("clearTimeout")?;
+        let mut started = Err(JsValue::UNDEFINED);
+        let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+            started = set_timeout.call2(&js_sys::global(), &resolve, &millis.into());
+        });
+        let id = started.map_err(|e| JsError::new(&format!("setTimeout failed: {e:?}")))?;
+        let _timer = Timer { id, clear_timeout };
+        let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    }
+    #[cfg(not(feature = "web"))]
+    tokio::time::sleep(duration).await;
+    Ok(())
+}
+
+/// Returns the function `name` of the global scope.
+#[cfg(feature = "web")]
+fn global_function(name: &str) -> JsResult<js_sys::Function> {
+    js_sys::Reflect::get(&js_sys::global(), &name.into())
+        .ok()
+        .and_then(|function| function.dyn_into::<js_sys::Function>().ok())
+        .ok_or_else(|| JsError::new(&format!("{name} is unavailable")))
+}
+
+/// A timer started with `setTimeout`, cleared when dropped.
+#[cfg(feature = "web")]
+struct Timer {
+    id: JsValue,
+    clear_timeout: js_sys::Function,
+}
+
+#[cfg(feature = "web")]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        // Clearing a timer that already fired does nothing.
+        let _ = self.clear_timeout.call1(&js_sys::global(), &self.id);
+    }
+}
@@ -0,0 +1,356 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The wallet session.
+//!
+//! When the wallet is encrypted, the key unlocking it (and the Secret vault) lives here
+//! rather than in the individual storage handles, so that locking the session wipes it
+//! for everyone at once. The session locks itself after a period of inactivity.
+//!
+//! Keys are kept per profile, and encrypted stores look theirs up by the ID of the
+//! profile they belong to, so that a client still running on another profile never
+//! seals its fields with the key of the selected one. Switching profiles locks the
+//! session.
+//!
+//! Locking also wipes the chains of every `Wallet` read from encrypted storage: such a
+//! wallet must be read again with `Wallet.get` once the session is unlocked.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use linera_base::time::{Duration, Instant};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    crypto::WalletKey,
+    profile::{self, Profile},
+    utils::WalletStorage as _,
+    wallet::schema,
+    JsError, JsResult,
+};
+
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+thread_local! {
+    static SESSION: RefCell<State> = RefCell::new(State::default());
+}
+
+struct State {
+    /// Whether the wallet of each profile is password-protected, once we have looked.
+    protected: HashMap<String, bool>,
+    /// The key of each unlocked profile.
+    keys: HashMap<String, Rc<WalletKey>>,
+    idle_timeout: Option<Duration>,
+    last_activity: Instant,
+    /// Bumped on every lock and unlock, to retire stale idle watchers.
+    generation: u64,
+    /// The lock hooks, by ID in registration order.
+    lock_hooks: BTreeMap<u64, Rc<dyn Fn()>>,
+    next_hook_id: u64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            protected: HashMap::new(),
+            keys: HashMap::new(),
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            last_activity: Instant::now(),
+            generation: 0,
+            lock_hooks: BTreeMap::new(),
+            next_hook_id: 0,
+        }
+    }
+}
+
+impl State {
+    fn is_locked(&self, profile_id: &str) -> bool {
+        self.protected.get(profile_id) == Some(&true) && !self.keys.contains_key(profile_id)
+    }
+}
+
+fn locked_error() -> JsError {
+    JsError::new("The wallet is locked")
+}
+
+/// Records user activity, postponing the idle lock.
+pub fn touch() {
+    SESSION.with_borrow_mut(|session| session.last_activity = Instant::now());
+}
+
+/// Whether the wallet of the selected profile is password-protected.
+///
+/// # Errors
+/// If storage is inaccessible.
+pub async fn is_protected() -> JsResult<bool> {
+    is_protected_for(&profile::current().await?).await
+}
+
+/// Whether the wallet of `profile` is password-protected, checking persistent storage
+/// the first time we are asked.
+///
+/// # Errors
+/// If storage is inaccessible.
+pub async fn is_protected_for(profile: &Profile) -> JsResult<bool> {
+    if let Some(protected) =
+        SESSION.with_borrow(|session| session.protected.get(&profile.id).copied())
+    {
+        return Ok(protected);
+    }
+    let protected = schema::storage_for(profile)
+        .read_kdf_params()
+        .await?
+        .is_some();
+    SESSION.with_borrow_mut(|session| session.protected.insert(profile.id.clone(), protected));
+    Ok(protected)
+}
+
+/// Fails if the session is locked for the selected profile, and records activity
+/// otherwise.
+///
+/// # Errors
+/// If the wallet is password-protected and the session is locked.
+pub async fn ensure_unlocked() -> JsResult<()> {
+    ensure_unlocked_for(&profile::current().await?).await
+}
+
+/// Fails if the session is locked for `profile`, and records activity otherwise.
+///
+/// # Errors
+/// If the wallet is password-protected and the session is locked.
+pub async fn ensure_unlocked_for(profile: &Profile) -> JsResult<()> {
+    if is_protected_for(profile).await?
+        && SESSION.with_borrow(|session| session.is_locked(&profile.id))
+    {
+        return Err(locked_error());
+    }
+    touch();
+    Ok(())
+}
+
+/// Returns the key of the profile `profile_id` in the unlocked session, recording
+/// activity.
+///
+/// # Errors
+/// If the session is locked for that profile.
+pub fn key(profile_id: &str) -> JsResult<Rc<WalletKey>> {
+    let key = SESSION
+        .with_borrow(|session| session.keys.get(profile_id).cloned())
+        .ok_or_else(locked_error)?;
+    touch();
+    Ok(key)
+}
+
+/// Unlocks the session for the profile `profile_id` with `key`, and starts watching
+/// for inactivity.
+pub(crate) fn set_key(profile_id: &str, key: Rc<WalletKey>) {
+    let generation = SESSION.with_borrow_mut(|session| {
+        session.protected.insert(profile_id.to_string(), true);
+        session.keys.insert(profile_id.to_string(), key);
+        session.last_activity = Instant::now();
+        session.generation += 1;
+        session.generation
+    });
+    spawn_idle_watcher(generation);
+}
+
+/// Where an encrypted store takes its key from.
+#[derive(Clone)]
+pub enum KeySource {
+    /// The session key of the profile with this ID, so that the store is unusable
+    /// while the session is locked.
+    Session(String),
+    /// A key given explicitly, e.g. a new one that only goes into the session once
+    /// the data sealed under it is written.
+    Key(Rc<WalletKey>),
+}
+
+impl std::fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Session(profile_id) => f.debug_tuple("Session").field(profile_id).finish(),
+            Self::Key(_) => f.write_str("Key(..)"),
+        }
+    }
+}
+
+impl KeySource {
+    /// Returns the key, recording activity if it is the session's.
+    ///
+    /// # Errors
+    /// If the key is the session's and the session is locked.
+    pub fn key(&self) -> JsResult<Rc<WalletKey>> {
+        match self {
+            Self::Session(profile_id) => key(profile_id),
+            Self::Key(key) => Ok(key.clone()),
+        }
+    }
+}
+
+/// Registers a hook run whenever the session locks, to wipe decrypted material held
+/// outside of the session, until the returned handle is dropped.
+#[must_use = "the hook is unregistered when the handle is dropped"]
+pub fn on_lock(hook: impl Fn() + 'static) -> LockHookHandle {
+    let id = SESSION.with_borrow_mut(|session| {
+        let id = session.next_hook_id;
+        session.next_hook_id += 1;
+        session.lock_hooks.insert(id, Rc::new(hook));
+        id
+    });
+    LockHookHandle { id }
+}
+
+/// A hook registered with `Session.onLock`, which runs until the handle is
+/// unregistered or freed.
+#[wasm_bindgen]
+pub struct LockHookHandle {
+    id: u64,
+}
+
+#[wasm_bindgen]
+impl LockHookHandle {
+    /// Unregisters the hook. Does nothing if it already was.
+    pub fn unregister(&self) {
+        // The session is gone if the thread is exiting: so are its hooks.
+        let _ = SESSION.try_with(|session| session.borrow_mut().lock_hooks.remove(&self.id));
+    }
+}
+
+impl Drop for LockHookHandle {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// Wipes the keys of every profile and runs the lock hooks.
+pub fn lock() {
+    let hooks = SESSION.with_borrow_mut(|session| {
+        // Dropping the last reference zeroes a key.
+        session.keys.clear();
+        session.generation += 1;
+        session.lock_hooks.values().cloned().collect::<Vec<_>>()
+    });
+    // Hooks may call back into the session, so run them without holding it.
+    for hook in hooks {
+        hook();
+    }
+    tracing::info!("wallet session locked");
+}
+
+fn spawn_idle_watcher(generation: u64) {
+    crate::runtime::spawn_local(async move {
+        loop {
+            let remaining = SESSION.with_borrow(|session| {
+                if session.generation != generation || session.keys.is_empty() {
+                    return None;
+                }
+                let timeout = session.idle_timeout?;
+                Some(timeout.saturating_sub(session.last_activity.elapsed()))
+            });
+            match remaining {
+                None => return,
+                Some(remaining) if remaining.is_zero() => {
+                    lock();
+                    return;
+                }
+                Some(remaining) => linera_base::time::timer::sleep(remaining).await,
+            }
+        }
+    });
+}
+
+/// The lock state of the wallet, exposed to JS.
+#[wasm_bindgen(js_name = "Session")]
+pub struct JsSession;
+
+#[wasm_bindgen(js_class = "Session")]
+impl JsSession {
+    /// Unlocks the wallet and the Secret vault of the selected profile with
+    /// `password`.
+    ///
+    /// Does nothing if the wallet isn't password-protected.
+    ///
+    /// # Errors
+    /// If storage is inaccessible or the password is incorrect.
+    pub async fn unlock(password: String) -> JsResult<()> {
+        let profile = profile::current().await?;
+        let Some(params) = schema::storage_for(&profile).read_kdf_params().await? else {
+            SESSION.with_borrow_mut(|session| session.protected.insert(profile.id, false));
+            return Ok(());
+        };
+        set_key(&profile.id, Rc::new(params.unlock(&password)?));
+        Ok(())
+    }
+
+    /// Locks the wallets of every profile, wiping the keys from memory.
+    #[wasm_bindgen(js_name = "lock")]
+    pub fn js_lock() {
+        lock();
+    }
+
+    /// Whether the wallet of the selected profile is password-protected and currently
+    /// locked.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    #[wasm_bindgen(js_name = "isLocked")]
+    pub async fn is_locked() -> JsResult<bool> {
+        let profile = profile::current().await?;
+        Ok(is_protected_for(&profile).await?
+            && SESSION.with_borrow(|session| session.is_locked(&profile.id)))
+    }
+
+    /// Sets the inactivity period after which the session locks itself, in
+    /// milliseconds. Zero disables the idle lock.
+    #[wasm_bindgen(js_name = "setIdleTimeout")]
+    pub fn set_idle_timeout(millis: u32) {
+        let timeout = (millis > 0).then(|| Duration::from_millis(millis.into()));
+        let generation = SESSION.with_borrow_mut(|session| {
+            session.idle_timeout = timeout;
+            session.generation += 1;
+            (!session.keys.is_empty()).then_some(session.generation)
+        });
+        if let Some(generation) = generation {
+            spawn_idle_watcher(generation);
+        }
+    }
+
+    /// Records user activity, postponing the idle lock.
+    #[wasm_bindgen(js_name = "touch")]
+    pub fn js_touch() {
+        touch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn lock_hooks_run_until_unregistered() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let handle = on_lock(move || counter.set(counter.get() + 1));
+        lock();
+        assert_eq!(calls.get(), 1);
+
+        handle.unregister();
+        lock();
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn dropping_the_handle_unregisters_the_hook() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        drop(on_lock(move || counter.set(counter.get() + 1)));
+        lock();
+        assert_eq!(calls.get(), 0);
+        assert!(SESSION.with_borrow(|session| session.lock_hooks.is_empty()));
+    }
+}
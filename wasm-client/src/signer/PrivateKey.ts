@@ -1,4 +1,4 @@
-import type { Signer } from './Signer.d.ts'
+import type { SignatureScheme, Signer } from './Signer.d.ts'
 export type { Signer } from './Signer.d.ts'
 import { Wallet, ethers } from 'ethers'
 
@@ -68,6 +68,10 @@ export default class PrivateKey implements Signer {
     return signature
   }
 
+  supportedSchemes(): SignatureScheme[] {
+    return ['secp256k1']
+  }
+
   async getPublicKey(owner: string): Promise<string> {
     if (
       typeof owner !== 'string' ||
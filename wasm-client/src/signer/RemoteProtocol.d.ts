@@ -0,0 +1,66 @@
+/**
+ * The messages a `RemoteSigner` exchanges over its `MessagePort`: JSON-RPC 2.0
+ * requests, and the answers the other side of the port sends back.
+ */
+
+/** The version of the protocol, sent in the params of every message. */
+export type RemoteSignerVersion = 1
+
+/** Asks whether the remote signer holds the key of `owner`. Answered with a boolean. */
+export interface ContainsKeyRequest {
+  jsonrpc: '2.0'
+  id: number
+  method: 'linera_containsKey'
+  params: { version: RemoteSignerVersion; owner: string }
+}
+
+/**
+ * Asks the remote signer to sign `value`, the hex of 32 bytes, with the key of
+ * `owner`. Answered with a `SignResult`.
+ */
+export interface SignRequest {
+  jsonrpc: '2.0'
+  id: number
+  method: 'linera_sign'
+  params: { version: RemoteSignerVersion; owner: string; value: string }
+}
+
+/** Tells the remote signer that the request `id` was given up on. */
+export interface CancelNotification {
+  jsonrpc: '2.0'
+  method: 'linera_cancel'
+  params: { version: RemoteSignerVersion; id: number }
+}
+
+export type RemoteSignerMessage = ContainsKeyRequest | SignRequest | CancelNotification
+
+/**
+ * The result of a `linera_sign` request: an EIP-191 signature for EVM addresses, or
+ * an Ed25519 signature along with the public key for 32-byte owners, all in hex.
+ */
+export interface SignResult {
+  signature: string
+  publicKey?: string
+}
+
+/**
+ * An error answer. Code 4001 means the user rejected the request, as in EIP-1193, and
+ * code -32090 that the remote signer gave up on it. `data.name` is reported as the
+ * error name.
+ */
+export interface RemoteSignerError {
+  jsonrpc: '2.0'
+  id: number
+  error: { code: number; message: string; data?: { name?: string } }
+}
+
+export interface RemoteSignerResult<T> {
+  jsonrpc: '2.0'
+  id: number
+  result: T
+}
+
+export type RemoteSignerAnswer =
+  | RemoteSignerResult<boolean>
+  | RemoteSignerResult<SignResult>
+  | RemoteSignerError
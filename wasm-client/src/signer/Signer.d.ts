@@ -1,22 +1,86 @@
 /**
- * Interface for signing and key management compatible with Ethereum (EVM) addresses.
+ * A signature scheme a `Signer` can sign with.
+ *
+ * - `secp256k1`: EIP-191 signatures, for owners that are EVM addresses.
+ * - `ed25519`: Ed25519 signatures, for 32-byte owners.
+ */
+export type SignatureScheme = 'secp256k1' | 'ed25519'
+
+/**
+ * The kind of a `SignerError`.
+ */
+export type SignerErrorKind =
+  | 'missingKey'
+  | 'signingError'
+  | 'publicKeyParse'
+  | 'jsConversion'
+  | 'unexpectedSignatureFormat'
+  | 'invalidAccountOwnerType'
+  | 'publicKeyMismatch'
+  | 'locked'
+  | 'keyStorage'
+  | 'userRejected'
+  | 'timeout'
+  | 'js'
+  | 'unknown'
+
+/**
+ * The error client calls fail with when signing fails.
+ */
+export interface SignerError extends Error {
+  name: 'SignerError'
+  kind: SignerErrorKind
+  /** The name of the error thrown by the JS signer, if any. */
+  jsName?: string
+  /** The message of the error thrown by the JS signer, if any. */
+  jsMessage?: string
+}
+
+/**
+ * Interface for signing and key management.
+ *
+ * Signers sign for EVM addresses with secp256k1 by default. Signers that also hold
+ * Ed25519 keys list `ed25519` in `supportedSchemes` and implement `getPublicKey`.
  */
 export interface Signer {
   /**
-   * Signs a given value using the private key associated with the specified EVM address.
-   * The signing process must follow the EIP-191 standard.
+   * Signs a given value using the private key associated with the specified owner.
    *
-   * @param owner - The EVM address whose private key will be used to sign the value.
+   * For an EVM address, the signing process must follow the EIP-191 standard. For a
+   * 32-byte owner, the value itself must be signed with Ed25519.
+   *
+   * @param owner - The owner whose private key will be used to sign the value.
    * @param value - The data to be signed, as a `Uint8Array`.
-   * @returns A promise that resolves to the EIP-191-compatible signature in hexadecimal string format.
+   * @returns A promise that resolves to the signature in hexadecimal string format.
+   * @throws An error named `UserRejected` (or with the EIP-1193 code 4001) if the user
+   * declines to sign, or named `TimeoutError` if signing takes too long. Other errors
+   * are reported with their name and message.
    */
   sign(owner: string, value: Uint8Array): Promise<string>
 
   /**
-   * Checks whether the instance holds a key whose associated address matches the given EVM address.
+   * Checks whether the instance holds a key whose associated owner matches the given one.
+   * This is only called for owners of the supported schemes.
    *
-   * @param owner - The EVM address to check for.
-   * @returns A promise that resolves to `true` if the key exists and matches the given address, otherwise `false`.
+   * @param owner - The owner to check for.
+   * @returns A promise that resolves to `true` if the key exists and matches the given owner, otherwise `false`.
    */
   containsKey(owner: string): Promise<boolean>
+
+  /**
+   * Lists the schemes the signer can sign with. Signers without this method only
+   * support `secp256k1`.
+   *
+   * This method must be synchronous: it is called while routing owners to signers,
+   * and a returned promise is treated as supporting no scheme.
+   */
+  supportedSchemes?(): SignatureScheme[]
+
+  /**
+   * Returns the public key of the given owner, in hexadecimal string format. Required
+   * to sign with Ed25519, as Ed25519 signatures are submitted along with their public key.
+   *
+   * @param owner - The owner whose public key to return.
+   */
+  getPublicKey?(owner: string): Promise<string>
 }
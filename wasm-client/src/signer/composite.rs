@@ -0,0 +1,492 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A signer combining several signers, so that one wallet can hold keys of different
+//! backends, e.g. a hardware-backed key for a valuable chain next to in-memory keys
+//! for throwaway ones.
+//!
+//! Each owner is routed to one of the registered signers: the one it was assigned to
+//! with `route`, or else the first one, in registration order, holding its key. Routes
+//! found that way are remembered until the signer is removed or stops holding the key.
+//! An assigned signer is still asked whether it holds the key, so that an owner whose
+//! key it lost isn't reported as held.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
+
+use linera_base::{
+    crypto::{AccountSignature, CryptoHash, Signer as _},
+    identifiers::AccountOwner,
+};
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "web")]
+use super::RemoteSigner;
+use super::{Error, InMemorySigner, JsSigner};
+use crate::{JsError, JsResult};
+
+/// A signer registered with a `CompositeSigner`.
+#[derive(Clone)]
+enum Child {
+    Js(JsSigner),
+    InMemory(InMemorySigner),
+    #[cfg(feature = "web")]
+    Remote(RemoteSigner),
+}
+
+impl Child {
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Error> {
+        match self {
+            // `JsSigner` has inherent methods of the same names.
+            Child::Js(signer) => linera_base::crypto::Signer::contains_key(signer, owner).await,
+            Child::InMemory(signer) => signer.contains_key(owner).await,
+            #[cfg(feature = "web")]
+            Child::Remote(signer) => signer.contains_key(owner).await,
+        }
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Error> {
+        match self {
+            Child::Js(signer) => linera_base::crypto::Signer::sign(signer, owner, value).await,
+            Child::InMemory(signer) => signer.sign(owner, value).await,
+            #[cfg(feature = "web")]
+            Child::Remote(signer) => signer.sign(owner, value).await,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The registered signers, by name, in registration order.
+    children: Vec<(String, Child)>,
+    /// Owners explicitly assigned to a signer.
+    routes: BTreeMap<AccountOwner, String>,
+    /// Owners found to be held by a signer.
+    found: BTreeMap<AccountOwner, String>,
+}
+
+impl State {
+    fn child(&self, name: &str) -> Option<&Child> {
+        self.children
+            .iter()
+            .find(|(child, _)| child == name)
+            .map(|(_, child)| child)
+    }
+}
+
+/// A signer routing each owner to one of several registered signers.
+///
+/// Clones share the same signers and routes.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct CompositeSigner {
+    state: Rc<RefCell<State>>,
+}
+
+impl CompositeSigner {
+    fn add(&self, name: String, child: Child) -> JsResult<()> {
+        let mut state = self.state.borrow_mut();
+        if state.child(&name).is_some() {
+            return Err(JsError::new(&format!(
+                "A signer named {name:?} is already registered"
+            )));
+        }
+        state.children.push((name, child));
+        Ok(())
+    }
+
+    /// Returns the name and signer `owner` is routed to, if known without asking the
+    /// signers, and whether the route was assigned explicitly.
+    fn known_route(&self, owner: &AccountOwner) -> Option<(String, Child, bool)> {
+        let state = self.state.borrow();
+        let (name, explicit) = match state.routes.get(owner) {
+            Some(name) => (name, true),
+            None => (state.found.get(owner)?, false),
+        };
+        let child = state.child(name)?.clone();
+        Some((name.clone(), child, explicit))
+    }
+
+    /// Finds the first signer holding the key of `owner`, remembering it.
+    async fn find(&self, owner: &AccountOwner) -> Result<Option<Child>, Error> {
+        // Don't hold the borrow while the signers are asked.
+        let children = self.state.borrow().children.clone();
+        for (name, child) in children {
+            if child.contains_key(owner).await? {
+                self.state.borrow_mut().found.insert(*owner, name);
+                return Ok(Some(child));
+            }
+        }
+        Ok(None)
+    }
+
+    fn forget_found(&self, owner: &AccountOwner) {
+        self.state.borrow_mut().found.remove(owner);
+    }
+}
+
+#[wasm_bindgen]
+impl CompositeSigner {
+    /// Creates a composite signer without any signers.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> CompositeSigner {
+        Self::default()
+    }
+
+    /// Registers a JS `Signer` under `name`.
+    ///
+    /// # Errors
+    /// If a signer is already registered under `name`.
+    #[wasm_bindgen(js_name = "addSigner")]
+    pub fn add_signer(&self, name: String, signer: JsSigner) -> JsResult<()> {
+        self.add(name, Child::Js(signer))
+    }
+
+    /// Registers an in-memory signer under `name`.
+    ///
+    /// # Errors
+    /// If a signer is already registered under `name`.
+    #[wasm_bindgen(js_name = "addInMemorySigner")]
+    pub fn add_in_memory_signer(&self, name: String, signer: &InMemorySigner) -> JsResult<()> {
+        self.add(name, Child::InMemory(signer.clone()))
+    }
+
+    /// Registers a remote signer under `name`.
+    ///
+    /// # Errors
+    /// If a signer is already registered under `name`.
+    #[cfg(feature = "web")]
+    #[wasm_bindgen(js_name = "addRemoteSigner")]
+    pub fn add_remote_signer(&self, name: String, signer: &RemoteSigner) -> JsResult<()> {
+        self.add(name, Child::Remote(signer.clone()))
+    }
+
+    /// Unregisters the signer named `name`, along with the routes to it. Returns
+    /// whether there was one.
+    pub fn remove(&self, name: &str) -> bool {
+        let mut state = self.state.borrow_mut();
+        let count = state.children.len();
+        state.children.retain(|(child, _)| child != name);
+        state.routes.retain(|_, route| route != name);
+        state.found.retain(|_, route| route != name);
+        state.children.len() != count
+    }
+
+    /// Assigns `owner` to the signer named `name`, which then always signs for it.
+    ///
+    /// # Errors
+    /// If no signer is registered under `name`, or if it doesn't hold the key of
+    /// `owner`.
+    pub async fn route(&self, owner: AccountOwner, name: String) -> JsResult<()> {
+        let child = self
+            .state
+            .borrow()
+            .child(&name)
+            .cloned()
+            .ok_or_else(|| JsError::new(&format!("No signer named {name:?}")))?;
+        if !child.contains_key(&owner).await? {
+            return Err(JsError::new(&format!(
+                "The signer named {name:?} doesn't hold the key of {owner}"
+            )));
+        }
+        // The signer may have been removed while it was asked.
+        let mut state = self.state.borrow_mut();
+        if state.child(&name).is_none() {
+            return Err(JsError::new(&format!("No signer named {name:?}")));
+        }
+        state.routes.insert(owner, name);
+        Ok(())
+    }
+
+    /// Removes the assignment of `owner` made with `route`, returning whether there
+    /// was one.
+    pub fn unroute(&self, owner: AccountOwner) -> bool {
+        self.state.borrow_mut().routes.remove(&owner).is_some()
+    }
+
+    /// Returns the names of the registered signers, in routing order.
+    #[must_use]
+    pub fn signers(&self) -> Vec<String> {
+        self.state
+            .borrow()
+            .children
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns the owners the signer named `name` holds keys for. For JS and remote
+    /// signers, which can't list their keys, these are the owners routed to them or
+    /// found to be held by them so far.
+    ///
+    /// # Errors
+    /// If no signer is registered under `name`, or if an in-memory signer is locked.
+    pub async fn owners(&self, name: String) -> JsResult<Vec<String>> {
+        let child = self
+            .state
+            .borrow()
+            .child(&name)
+            .cloned()
+            .ok_or_else(|| JsError::new(&format!("No signer named {name:?}")))?;
+        if let Child::InMemory(signer) = child {
+            return signer.owners().await;
+        }
+        let state = self.state.borrow();
+        Ok(state
+            .routes
+            .iter()
+            .chain(&state.found)
+            .filter(|(_, route)| **route == name)
+            .map(|(owner, _)| *owner)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|owner| owner.to_string())
+            .collect())
+    }
+
+    /// Returns the name of the signer `owner` is routed to, if any signer holds its
+    /// key.
+    ///
+    /// # Errors
+    /// If a signer fails to tell whether it holds the key.
+    #[wasm_bindgen(js_name = "signerOf")]
+    pub async fn signer_of(&self, owner: AccountOwner) -> JsResult<Option<String>> {
+        if !linera_base::crypto::Signer::contains_key(self, &owner).await? {
+            return Ok(None);
+        }
+        Ok(self.known_route(&owner).map(|(name, _, _)| name))
+    }
+}
+
+impl linera_base::crypto::Signer for CompositeSigner {
+    type Error = Error;
+
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        if let Some((_, child, explicit)) = self.known_route(owner) {
+            let contains_key = child.contains_key(owner).await?;
+            // An assigned signer is the only one signing for the owner.
+            if explicit || contains_key {
+                return Ok(contains_key);
+            }
+            self.forget_found(owner);
+        }
+        Ok(self.find(owner).await?.is_some())
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Self::Error> {
+        if let Some((_, child, explicit)) = self.known_route(owner) {
+            match child.sign(owner, value).await {
+                // The signer may have lost the key since it was found.
+                Err(Error::MissingKey) if !explicit => self.forget_found(owner),
+                result => return result,
+            }
+        }
+        let child = self.find(owner).await?.ok_or(Error::MissingKey)?;
+        child.sign(owner, value).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use linera_base::crypto::{AccountSecretKey, Signer as _};
+
+    use super::*;
+    use crate::signer::KeyScheme;
+
+    fn key(scheme: KeyScheme, byte: u8) -> AccountSecretKey {
+        scheme.secret_key(&[byte; 32]).unwrap()
+    }
+
+    fn owner(key: &AccountSecretKey) -> AccountOwner {
+        AccountOwner::from(key.public())
+    }
+
+    /// Returns a composite signer with an Ed25519 key in `ed25519` and a secp256k1 one
+    /// in `secp256k1`, along with the two keys.
+    fn composite() -> (CompositeSigner, AccountSecretKey, AccountSecretKey) {
+        let signer = CompositeSigner::new();
+        signer
+            .add_in_memory_signer(
+                "ed25519".to_string(),
+                &InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]),
+            )
+            .unwrap();
+        signer
+            .add_in_memory_signer(
+                "secp256k1".to_string(),
+                &InMemorySigner::with_keys([key(KeyScheme::Secp256k1, 2)]),
+            )
+            .unwrap();
+        (
+            signer,
+            key(KeyScheme::Ed25519, 1),
+            key(KeyScheme::Secp256k1, 2),
+        )
+    }
+
+    #[test]
+    fn owners_are_routed_to_the_signer_holding_their_key() {
+        block_on(async {
+            let (signer, ed25519, secp256k1) = composite();
+            let value = CryptoHash::from([7; 4]);
+            for (key, name) in [(&ed25519, "ed25519"), (&secp256k1, "secp256k1")] {
+                let owner = owner(key);
+                assert!(signer.contains_key(&owner).await.unwrap());
+                assert_eq!(
+                    signer.signer_of(owner).await.unwrap().as_deref(),
+                    Some(name)
+                );
+                assert_eq!(
+                    signer.sign(&owner, &value).await.unwrap(),
+                    key.sign_prehash(value)
+                );
+            }
+            let unknown = owner(&key(KeyScheme::Ed25519, 3));
+            assert!(!signer.contains_key(&unknown).await.unwrap());
+            assert_eq!(
+                signer.sign(&unknown, &value).await.unwrap_err(),
+                Error::MissingKey
+            );
+        });
+    }
+
+    #[test]
+    fn explicit_routes_take_precedence() {
+        block_on(async {
+            let shared = key(KeyScheme::Ed25519, 1);
+            let signer = CompositeSigner::new();
+            let first = InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]);
+            let second = InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]);
+            signer
+                .add_in_memory_signer("first".to_string(), &first)
+                .unwrap();
+            signer
+                .add_in_memory_signer("second".to_string(), &second)
+                .unwrap();
+            let owner = owner(&shared);
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("first")
+            );
+            signer.route(owner, "second".to_string()).await.unwrap();
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("second")
+            );
+            // Only the assigned signer signs, even if the other one holds the key.
+            second.forget(&owner);
+            assert_eq!(
+                signer
+                    .sign(&owner, &CryptoHash::from([7; 4]))
+                    .await
+                    .unwrap_err(),
+                Error::MissingKey
+            );
+            assert!(signer.unroute(owner));
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("first")
+            );
+        });
+    }
+
+    #[test]
+    fn routes_need_the_key_and_a_registered_signer() {
+        block_on(async {
+            let (signer, ed25519, _) = composite();
+            let owner = owner(&ed25519);
+            assert!(signer.route(owner, "secp256k1".to_string()).await.is_err());
+            assert!(signer.route(owner, "none".to_string()).await.is_err());
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("ed25519")
+            );
+        });
+    }
+
+    #[test]
+    fn removing_a_signer_drops_its_routes() {
+        block_on(async {
+            let (signer, ed25519, secp256k1) = composite();
+            let (ed25519, secp256k1) = (owner(&ed25519), owner(&secp256k1));
+            signer.route(ed25519, "ed25519".to_string()).await.unwrap();
+            assert!(signer.contains_key(&secp256k1).await.unwrap());
+            assert!(signer.remove("ed25519"));
+            assert!(!signer.remove("ed25519"));
+            assert_eq!(signer.signers(), ["secp256k1"]);
+            assert!(!signer.unroute(ed25519));
+            assert!(!signer.contains_key(&ed25519).await.unwrap());
+            assert_eq!(signer.signer_of(ed25519).await.unwrap(), None);
+            assert!(signer.contains_key(&secp256k1).await.unwrap());
+        });
+    }
+
+    #[test]
+    fn a_stale_route_does_not_report_the_key() {
+        block_on(async {
+            let shared = key(KeyScheme::Ed25519, 1);
+            let signer = CompositeSigner::new();
+            let routed = InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]);
+            signer
+                .add_in_memory_signer("routed".to_string(), &routed)
+                .unwrap();
+            signer
+                .add_in_memory_signer(
+                    "other".to_string(),
+                    &InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]),
+                )
+                .unwrap();
+            let owner = owner(&shared);
+            signer.route(owner, "routed".to_string()).await.unwrap();
+            routed.forget(&owner);
+            assert!(!signer.contains_key(&owner).await.unwrap());
+            assert_eq!(signer.signer_of(owner).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn a_found_route_is_forgotten_once_the_key_is_lost() {
+        block_on(async {
+            let shared = key(KeyScheme::Ed25519, 1);
+            let signer = CompositeSigner::new();
+            let first = InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]);
+            signer
+                .add_in_memory_signer("first".to_string(), &first)
+                .unwrap();
+            signer
+                .add_in_memory_signer(
+                    "second".to_string(),
+                    &InMemorySigner::with_keys([key(KeyScheme::Ed25519, 1)]),
+                )
+                .unwrap();
+            let owner = owner(&shared);
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("first")
+            );
+            first.forget(&owner);
+            assert_eq!(
+                signer.signer_of(owner).await.unwrap().as_deref(),
+                Some("second")
+            );
+            assert_eq!(
+                signer.owners("second".to_string()).await.unwrap(),
+                // In-memory signers list their own keys.
+                [owner.to_string()]
+            );
+        });
+    }
+}
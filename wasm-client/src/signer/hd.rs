@@ -0,0 +1,418 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hierarchical deterministic keys for the in-memory signer: BIP-39 mnemonics, and
+//! the derivation of any number of owners from them.
+//!
+//! secp256k1 keys are derived with BIP-32 along the Ethereum BIP-44 path
+//! `m/44'/60'/0'/0/{index}`, so that the first owner is the address ethers derives
+//! from the same phrase. Ed25519 keys are derived with SLIP-10, which only supports
+//! hardened derivation, along `m/44'/60'/0'/0'/{index}'`.
+//!
+//! The mnemonic is kept in the Secret vault next to the keys derived from it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use hmac::{Hmac, Mac as _};
+use linera_base::{
+    crypto::AccountSecretKey,
+    identifiers::{AccountOwner, ChainId},
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize as _, Zeroizing};
+
+use super::{InMemorySigner, KeyScheme};
+use crate::{
+    crypto, session,
+    utils::{SecretVault, WalletStorage as _},
+    Client, JsError, JsResult,
+};
+
+/// The vault field holding the mnemonic of the in-memory signer.
+const MNEMONIC_FIELD: &str = "signer:mnemonic";
+
+/// How many consecutive owners without chains end account discovery, as in BIP-44.
+const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// The first hardened child index.
+const HARDENED: u32 = 1 << 31;
+
+type Seed = Zeroizing<[u8; 64]>;
+
+/// A mnemonic as stored in the vault.
+#[derive(Serialize, Deserialize)]
+struct StoredMnemonic {
+    phrase: String,
+    #[serde(default)]
+    passphrase: String,
+}
+
+impl StoredMnemonic {
+    fn seed(&self) -> JsResult<Seed> {
+        let mnemonic = bip39::Mnemonic::parse(&self.phrase)
+            .map_err(|e| JsError::new(&format!("Invalid mnemonic: {e}")))?;
+        Ok(Zeroizing::new(mnemonic.to_seed(&self.passphrase)))
+    }
+}
+
+impl Drop for StoredMnemonic {
+    fn drop(&mut self) {
+        self.phrase.zeroize();
+        self.passphrase.zeroize();
+    }
+}
+
+/// Options of `InMemorySigner.discover`.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryOptions {
+    /// The schemes to derive owners of. Defaults to secp256k1 only.
+    #[serde(default)]
+    pub schemes: Option<Vec<KeyScheme>>,
+    /// The chains to look for owners in. Defaults to the chains of the wallet.
+    #[serde(default)]
+    pub chains: Option<Vec<ChainId>>,
+    /// How many consecutive owners without chains end the discovery of a scheme.
+    /// Defaults to 20.
+    #[serde(default)]
+    pub gap_limit: Option<u32>,
+}
+
+/// An owner found by account discovery, and the chains it owns.
+#[derive(Serialize, tsify::Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredOwner {
+    pub owner: AccountOwner,
+    pub scheme: KeyScheme,
+    /// The index the owner is derived at.
+    pub index: u32,
+    pub chains: Vec<ChainId>,
+}
+
+/// Generates a new BIP-39 mnemonic of `word_count` words: 12 (the default), 15, 18,
+/// 21 or 24.
+///
+/// # Errors
+/// If the word count is invalid or no randomness is available.
+#[wasm_bindgen(js_name = "generateMnemonic")]
+pub fn generate_mnemonic(word_count: Option<usize>) -> JsResult<String> {
+    let word_count = word_count.unwrap_or(12);
+    if !matches!(word_count, 12 | 15 | 18 | 21 | 24) {
+        return Err(JsError::new("A mnemonic has 12, 15, 18, 21 or 24 words"));
+    }
+    let entropy = Zeroizing::new(crypto::random_bytes::<32>()?);
+    let mnemonic = bip39::Mnemonic::from_entropy(&entropy[..word_count / 3 * 4])
+        .map_err(|e| JsError::new(&format!("Failed to generate a mnemonic: {e}")))?;
+    Ok(mnemonic.to_string())
+}
+
+/// Checks that `phrase` is a valid BIP-39 mnemonic, including its checksum.
+#[wasm_bindgen(js_name = "validateMnemonic")]
+#[must_use]
+pub fn validate_mnemonic(phrase: &str) -> bool {
+    bip39::Mnemonic::parse(phrase).is_ok()
+}
+
+/// Computes HMAC-SHA512 of the concatenation of `parts` under `key`.
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Zeroizing<[u8; 64]> {
+    let mut mac = Hmac::<sha2::Sha512>::new_from_slice(key).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    let mut output = Zeroizing::new([0u8; 64]);
+    output.copy_from_slice(&mac.finalize().into_bytes());
+    output
+}
+
+/// Derives an Ed25519 secret key along a path of hardened indices, as in SLIP-10.
+fn slip10_ed25519(seed: &[u8], path: &[u32]) -> Zeroizing<[u8; 32]> {
+    let mut node = hmac_sha512(b"ed25519 seed", &[seed]);
+    for index in path {
+        let (key, chain_code) = node.split_at(32);
+        node = hmac_sha512(chain_code, &[&[0], key, &(index | HARDENED).to_be_bytes()]);
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&node[..32]);
+    key
+}
+
+/// Derives a secp256k1 secret key along `path`, as in BIP-32.
+fn bip32_secp256k1(seed: &[u8], path: &str) -> JsResult<Zeroizing<[u8; 32]>> {
+    let path: bip32::DerivationPath = path
+        .parse()
+        .map_err(|e| JsError::new(&format!("Invalid derivation path: {e}")))?;
+    let key = bip32::XPrv::derive_from_path(seed, &path)
+        .map_err(|e| JsError::new(&format!("Failed to derive a key: {e}")))?;
+    Ok(Zeroizing::new(key.to_bytes()))
+}
+
+/// Derives the key of `scheme` at `index` from `seed`.
+fn derive(seed: &Seed, scheme: KeyScheme, index: u32) -> JsResult<AccountSecretKey> {
+    if index >= HARDENED {
+        return Err(JsError::new(&format!(
+            "Derivation indices must be lower than {HARDENED}"
+        )));
+    }
+    let bytes = match scheme {
+        KeyScheme::Secp256k1 => {
+            bip32_secp256k1(seed.as_slice(), &format!("m/44'/60'/0'/0/{index}"))?
+        }
+        KeyScheme::Ed25519 => slip10_ed25519(seed.as_slice(), &[44, 60, 0, 0, index]),
+    };
+    Ok(scheme.secret_key(&bytes)?)
+}
+
+impl InMemorySigner {
+    /// Reads the seed of the stored mnemonic.
+    async fn seed() -> JsResult<Seed> {
+        session::ensure_unlocked().await?;
+        let value = SecretVault::storage()
+            .await?
+            .read_field(MNEMONIC_FIELD)
+            .await?
+            .ok_or_else(|| JsError::new("The signer has no mnemonic"))?;
+        let mnemonic: StoredMnemonic = serde_json::from_value(value)
+            .map_err(|e| JsError::new(&format!("Malformed signer mnemonic: {e}")))?;
+        mnemonic.seed()
+    }
+
+    /// Finds the owners derived from the mnemonic that own chains, adding their keys
+    /// to the signer, and returns them.
+    ///
+    /// Owners are derived at successive indices for each scheme, until `gapLimit`
+    /// consecutive ones own none of the chains. Chains of the wallet without an owner
+    /// are given the first owner found for them, and the wallet is saved.
+    ///
+    /// Chains that can't be synchronized are skipped with a warning.
+    ///
+    /// # Errors
+    /// If the signer has no mnemonic, if the session is locked, or if the keys or the
+    /// wallet can't be saved.
+    pub async fn discover_owners(
+        &self,
+        client: &Client,
+        options: Option<DiscoveryOptions>,
+    ) -> JsResult<Vec<DiscoveredOwner>> {
+        let options = options.unwrap_or_default();
+        let wallet = &client.persistent.wallet;
+        // Fail before synchronizing any chain if there is no mnemonic.
+        let seed = Self::seed().await?;
+        let chain_ids = options.chains.unwrap_or_else(|| wallet.chains.chain_ids());
+        let mut owners = BTreeMap::new();
+        for chain_id in chain_ids {
+            match chain_owners(client, chain_id).await {
+                Ok(chain_owners) => {
+                    owners.insert(chain_id, chain_owners);
+                }
+                Err(error) => tracing::warn!(
+                    "skipping chain {chain_id} in account discovery: {}",
+                    error.message()
+                ),
+            }
+        }
+
+        let gap_limit = options.gap_limit.unwrap_or(DEFAULT_GAP_LIMIT);
+        let mut discovered = Vec::new();
+        for scheme in options
+            .schemes
+            .unwrap_or_else(|| vec![KeyScheme::Secp256k1])
+        {
+            let (mut index, mut gap) = (0, 0);
+            while gap < gap_limit && index < HARDENED {
+                let key = derive(&seed, scheme, index)?;
+                let owner = AccountOwner::from(key.public());
+                let chains = owners
+                    .iter()
+                    .filter(|(_, owners)| owners.contains(&owner))
+                    .map(|(chain_id, _)| *chain_id)
+                    .collect::<Vec<_>>();
+                if chains.is_empty() {
+                    gap += 1;
+                } else {
+                    gap = 0;
+                    self.insert(key).await?;
+                    discovered.push(DiscoveredOwner {
+                        owner,
+                        scheme,
+                        index,
+                        chains,
+                    });
+                }
+                index += 1;
+            }
+        }
+
+        let mut updated = false;
+        for found in &discovered {
+            for chain_id in &found.chains {
+                if wallet
+                    .chains
+                    .get(*chain_id)
+                    .is_some_and(|chain| chain.owner.is_none())
+                {
+                    wallet
+                        .chains
+                        .mutate(*chain_id, |chain| chain.owner = Some(found.owner));
+                    updated = true;
+                }
+            }
+        }
+        if updated {
+            client.persistent.save_to_storage(false).await?;
+        }
+        Ok(discovered)
+    }
+}
+
+/// Reads the owners of `chain_id`, synchronizing it first.
+async fn chain_owners(client: &Client, chain_id: ChainId) -> JsResult<BTreeSet<AccountOwner>> {
+    let chain_client = client
+        .client_context
+        .lock()
+        .await
+        .make_chain_client(chain_id)
+        .await?;
+    chain_client.synchronize_from_validators().await?;
+    let ownership = chain_client.query_chain_ownership().await?;
+    Ok(ownership
+        .super_owners
+        .into_iter()
+        .chain(ownership.owners.into_keys())
+        .collect())
+}
+
+#[wasm_bindgen]
+impl InMemorySigner {
+    /// Sets the mnemonic owners are derived from, with an optional BIP-39
+    /// passphrase, and saves it in the vault. Keys already held by the signer are
+    /// kept.
+    ///
+    /// # Errors
+    /// If the phrase isn't a valid mnemonic, if the session is locked or if the
+    /// mnemonic can't be saved.
+    #[wasm_bindgen(js_name = "setMnemonic")]
+    pub async fn set_mnemonic(&self, phrase: String, passphrase: Option<String>) -> JsResult<()> {
+        let mnemonic = StoredMnemonic {
+            phrase,
+            passphrase: passphrase.unwrap_or_default(),
+        };
+        // Fail early on invalid phrases.
+        mnemonic.seed()?;
+        session::ensure_unlocked().await?;
+        let value = serde_json::to_value(&mnemonic)
+            .map_err(|e| JsError::new(&format!("Failed to serialize the mnemonic: {e}")))?;
+        SecretVault::storage()
+            .await?
+            .write_field(MNEMONIC_FIELD, value)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns whether the signer has a mnemonic to derive owners from.
+    ///
+    /// # Errors
+    /// If the session is locked.
+    #[wasm_bindgen(js_name = "hasMnemonic")]
+    pub async fn has_mnemonic(&self) -> JsResult<bool> {
+        session::ensure_unlocked().await?;
+        Ok(SecretVault::storage()
+            .await?
+            .read_field(MNEMONIC_FIELD)
+            .await?
+            .is_some())
+    }
+
+    /// Derives the owner of `scheme` at `index` from the mnemonic, adds its key to the
+    /// signer and returns it.
+    ///
+    /// # Errors
+    /// If the signer has no mnemonic, if the session is locked or if the key can't be
+    /// saved.
+    #[wasm_bindgen(js_name = "deriveOwner")]
+    pub async fn derive_owner(&self, scheme: KeyScheme, index: u32) -> JsResult<String> {
+        let key = derive(&Self::seed().await?, scheme, index)?;
+        Ok(self.insert(key).await?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test vectors of SLIP-10 and BIP-32 share their first seed.
+    const SEED_1: &str = "000102030405060708090a0b0c0d0e0f";
+    const SEED_2: &str = "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542";
+
+    #[test]
+    fn slip10_ed25519_test_vectors() {
+        let cases: &[(&str, &[u32], &str)] = &[
+            (
+                SEED_1,
+                &[],
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            ),
+            (
+                SEED_1,
+                &[0],
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            ),
+            (
+                SEED_1,
+                &[0, 1, 2, 2, 1_000_000_000],
+                "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            ),
+            (
+                SEED_2,
+                &[],
+                "171cb88b1b3c1db25add599712e36245d75bc65a1a5c9e18d76f9f2b1eab4012",
+            ),
+            (
+                SEED_2,
+                &[0],
+                "1559eb2bbec5790b0c65d8693e4d0875b1747f4970ae8b650486ed7470845635",
+            ),
+        ];
+        for (seed, path, expected) in cases {
+            let key = slip10_ed25519(&hex::decode(seed).unwrap(), path);
+            assert_eq!(hex::encode(*key), *expected, "path {path:?}");
+        }
+    }
+
+    #[test]
+    fn bip32_secp256k1_test_vectors() {
+        let cases = [
+            (
+                "m",
+                "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35",
+            ),
+            (
+                "m/0'",
+                "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            ),
+            (
+                "m/0'/1/2'/2/1000000000",
+                "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8",
+            ),
+        ];
+        for (path, expected) in cases {
+            let key = bip32_secp256k1(&hex::decode(SEED_1).unwrap(), path).unwrap();
+            assert_eq!(hex::encode(*key), expected, "path {path}");
+        }
+    }
+
+    #[test]
+    fn first_secp256k1_owner_is_the_ethers_address() {
+        // The default account of Hardhat and Anvil, as derived by ethers.
+        let mnemonic = StoredMnemonic {
+            phrase: "test test test test test test test test test test test junk".to_string(),
+            passphrase: String::new(),
+        };
+        let key = derive(&mnemonic.seed().unwrap(), KeyScheme::Secp256k1, 0).unwrap();
+        assert_eq!(
+            AccountOwner::from(key.public()).to_string().to_lowercase(),
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+        );
+    }
+}
@@ -1,2 +1,3 @@
-export type { Signer } from './Signer.d.ts';
-export { default as PrivateKey } from './PrivateKey.js';
\ No newline at end of file
+export type { SignatureScheme, Signer, SignerError, SignerErrorKind } from './Signer.d.ts';
+export type * from './RemoteProtocol.d.ts';
+export { default as PrivateKey } from './PrivateKey.js';
@@ -0,0 +1,248 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A signer holding its keys in wasm memory, so that private keys never reach the JS
+//! heap.
+//!
+//! The keys are persisted in the Secret vault, and so are encrypted at rest if the
+//! wallet is password-protected. They are loaded when first needed, and dropped
+//! (which zeroes them) whenever the session locks, to be loaded again once it is
+//! unlocked.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+};
+
+use linera_base::{
+    crypto::{
+        AccountSecretKey, AccountSignature, CryptoError, CryptoHash, Ed25519SecretKey, EvmSecretKey,
+    },
+    identifiers::AccountOwner,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use super::Error;
+use crate::{
+    crypto, session,
+    utils::{SecretVault, WalletStorage as _},
+    JsError, JsResult,
+};
+
+/// The vault field holding the keys of the in-memory signer.
+const KEYS_FIELD: &str = "signer:keys";
+
+type Keys = BTreeMap<AccountOwner, AccountSecretKey>;
+
+/// A signature scheme for new keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyScheme {
+    /// secp256k1 keys used the Ethereum way: EIP-191 signatures and 20-byte
+    /// addresses, like the JS `PrivateKey` signer.
+    Secp256k1,
+    /// Ed25519 keys, with 32-byte owners.
+    Ed25519,
+}
+
+impl KeyScheme {
+    /// Makes a secret key of this scheme from 32 bytes of secret material.
+    ///
+    /// # Errors
+    /// If the bytes aren't a valid key for the scheme.
+    pub(crate) fn secret_key(self, bytes: &[u8; 32]) -> Result<AccountSecretKey, CryptoError> {
+        Ok(match self {
+            KeyScheme::Secp256k1 => {
+                AccountSecretKey::EvmSecp256k1(EvmSecretKey::from_bytes(bytes)?)
+            }
+            KeyScheme::Ed25519 => AccountSecretKey::Ed25519(Ed25519SecretKey::from_bytes(bytes)),
+        })
+    }
+}
+
+/// A signer holding its keys in wasm memory, exposed to JS.
+///
+/// Clones share the same keys.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct InMemorySigner {
+    /// The keys, or `None` until they are loaded from the vault and after the session
+    /// locks.
+    keys: Rc<RefCell<Option<Keys>>>,
+    /// Wipes the keys when the session locks, as long as a clone is alive.
+    _lock_hook: Rc<session::LockHookHandle>,
+}
+
+impl InMemorySigner {
+    /// Makes sure the keys are loaded, reading them from the vault if needed.
+    async fn load(&self) -> Result<(), Error> {
+        if self.keys.borrow().is_some() {
+            return Ok(());
+        }
+        session::ensure_unlocked()
+            .await
+            .map_err(|_| Error::Locked)?;
+        let keys = Self::read_keys().await.map_err(|error| Error::KeyStorage {
+            message: error.message(),
+        })?;
+        *self.keys.borrow_mut() = Some(keys);
+        Ok(())
+    }
+
+    async fn read_keys() -> JsResult<Keys> {
+        let Some(value) = SecretVault::storage().await?.read_field(KEYS_FIELD).await? else {
+            return Ok(Keys::new());
+        };
+        serde_json::from_value(value)
+            .map_err(|e| JsError::new(&format!("Malformed signer keys: {e}")))
+    }
+
+    /// Applies `change` to the keys and writes them back to the vault.
+    async fn update<R>(&self, change: impl FnOnce(&mut Keys) -> R) -> JsResult<R> {
+        self.load().await?;
+        let (result, value) = {
+            let mut keys = self.keys.borrow_mut();
+            let keys = keys.as_mut().ok_or(Error::Locked)?;
+            let result = change(keys);
+            let value = serde_json::to_value(&*keys)
+                .map_err(|e| JsError::new(&format!("Failed to serialize signer keys: {e}")))?;
+            (result, value)
+        };
+        SecretVault::storage()
+            .await?
+            .write_field(KEYS_FIELD, value)
+            .await?;
+        Ok(result)
+    }
+
+    /// Adds a key, returning its owner.
+    ///
+    /// # Errors
+    /// If the keys can't be saved.
+    pub(crate) async fn insert(&self, key: AccountSecretKey) -> JsResult<AccountOwner> {
+        let owner = AccountOwner::from(key.public());
+        self.update(|keys| keys.insert(owner, key)).await?;
+        Ok(owner)
+    }
+
+    /// Creates a signer holding `keys`, without reading the vault.
+    #[cfg(test)]
+    pub(crate) fn with_keys(keys: impl IntoIterator<Item = AccountSecretKey>) -> Self {
+        let signer = Self::new();
+        *signer.keys.borrow_mut() = Some(
+            keys.into_iter()
+                .map(|key| (AccountOwner::from(key.public()), key))
+                .collect(),
+        );
+        signer
+    }
+
+    /// Drops the key of `owner` from memory, without touching the vault.
+    #[cfg(test)]
+    pub(crate) fn forget(&self, owner: &AccountOwner) {
+        if let Some(keys) = self.keys.borrow_mut().as_mut() {
+            keys.remove(owner);
+        }
+    }
+
+    fn on_lock(keys: &Weak<RefCell<Option<Keys>>>) {
+        if let Some(keys) = keys.upgrade() {
+            // Dropping the keys zeroes them.
+            keys.borrow_mut().take();
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl InMemorySigner {
+    /// Creates a signer using the keys in the Secret vault of the selected profile.
+    /// The keys are only read when first needed.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> InMemorySigner {
+        let keys = Rc::new(RefCell::new(None));
+        let weak = Rc::downgrade(&keys);
+        let lock_hook = session::on_lock(move || Self::on_lock(&weak));
+        InMemorySigner {
+            keys,
+            _lock_hook: Rc::new(lock_hook),
+        }
+    }
+
+    /// Generates a new key of the given scheme, saves it and returns its owner.
+    ///
+    /// # Errors
+    /// If the session is locked or the key can't be saved.
+    pub async fn generate(&self, scheme: KeyScheme) -> JsResult<String> {
+        let bytes = Zeroizing::new(crypto::random_bytes::<32>()?);
+        let key = scheme.secret_key(&bytes)?;
+        Ok(self.insert(key).await?.to_string())
+    }
+
+    /// Removes the key of `owner`, returning whether there was one.
+    ///
+    /// # Errors
+    /// If the session is locked or the keys can't be saved.
+    pub async fn remove(&self, owner: AccountOwner) -> JsResult<bool> {
+        self.update(|keys| keys.remove(&owner).is_some()).await
+    }
+
+    /// Returns the owners of the keys held by the signer.
+    ///
+    /// # Errors
+    /// If the session is locked.
+    pub async fn owners(&self) -> JsResult<Vec<String>> {
+        self.load().await?;
+        Ok(self
+            .keys
+            .borrow()
+            .iter()
+            .flat_map(BTreeMap::keys)
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    /// Drops the keys from memory, zeroing them. They are read again from the vault
+    /// when next needed.
+    pub fn lock(&self) {
+        self.keys.borrow_mut().take();
+    }
+}
+
+impl Default for InMemorySigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl linera_base::crypto::Signer for InMemorySigner {
+    type Error = Error;
+
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        self.load().await?;
+        Ok(self
+            .keys
+            .borrow()
+            .as_ref()
+            .is_some_and(|keys| keys.contains_key(owner)))
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Self::Error> {
+        self.load().await?;
+        let keys = self.keys.borrow();
+        let key = keys
+            .as_ref()
+            .ok_or(Error::Locked)?
+            .get(owner)
+            .ok_or(Error::MissingKey)?;
+        Ok(key.sign_prehash(*value))
+    }
+}
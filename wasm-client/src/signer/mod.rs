@@ -4,27 +4,147 @@
 //! This module contains various implementation of the [`Signer`] trait usable in the browser.
 use std::fmt::Display;
 
+mod composite;
+mod hd;
+mod memory;
+#[cfg(feature = "web")]
+pub mod remote;
+
+pub use composite::CompositeSigner;
+pub use hd::{generate_mnemonic, validate_mnemonic, DiscoveredOwner, DiscoveryOptions};
+pub use memory::{InMemorySigner, KeyScheme};
+#[cfg(feature = "web")]
+pub use remote::{RemoteSigner, RemoteSignerOptions};
+
 use linera_base::{
-    crypto::{AccountSignature, CryptoHash},
+    crypto::{AccountSignature, CryptoHash, Ed25519PublicKey, Ed25519Signature},
     identifiers::AccountOwner,
 };
 use wasm_bindgen::prelude::*;
-use web_sys::wasm_bindgen;
 
-// TODO(#5150) remove this in favour of passing up arbitrary `JsValue` errors
+/// The JS error names signers throw when the user declines to sign.
+const USER_REJECTED_NAMES: &[&str] = &["UserRejected", "UserRejectedError", "NotAllowedError"];
+/// The JS error names signers throw when signing takes too long.
+const TIMEOUT_NAMES: &[&str] = &["Timeout", "TimeoutError"];
+/// The EIP-1193 error code for requests rejected by the user.
+const USER_REJECTED_CODE: f64 = 4001.0;
+
+/// The numeric codes signers used to throw, before signer errors kept the JS error.
+/// Signers throwing them are still understood.
+///
+/// @deprecated Throw an `Error` instead: its name and message are kept, and client
+/// calls fail with a `SignerError` whose `kind` tells what went wrong.
 #[repr(u8)]
 #[wasm_bindgen(js_name = "SignerError")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Error {
+pub enum ErrorCode {
     MissingKey = 0,
     SigningError = 1,
     PublicKeyParse = 2,
     JsConversion = 3,
     UnexpectedSignatureFormat = 4,
     InvalidAccountOwnerType = 5,
+    Locked = 6,
     Unknown = 9,
 }
 
+impl From<ErrorCode> for Error {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::MissingKey => Error::MissingKey,
+            ErrorCode::SigningError => Error::SigningError,
+            ErrorCode::PublicKeyParse => Error::PublicKeyParse,
+            ErrorCode::JsConversion => Error::JsConversion,
+            ErrorCode::UnexpectedSignatureFormat => Error::UnexpectedSignatureFormat,
+            ErrorCode::InvalidAccountOwnerType => Error::InvalidAccountOwnerType,
+            ErrorCode::Locked => Error::Locked,
+            ErrorCode::Unknown => Error::Unknown,
+        }
+    }
+}
+
+/// An error from a signer.
+///
+/// Errors thrown by JS signers keep their name and message. Surfaced to JS, they
+/// become an `Error` named `SignerError`, with a `kind` property set to
+/// [`Error::kind`], and `jsName` and `jsMessage` properties for errors thrown by JS.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Error {
+    MissingKey,
+    SigningError,
+    PublicKeyParse,
+    JsConversion,
+    UnexpectedSignatureFormat,
+    InvalidAccountOwnerType,
+    /// The public key given by a JS signer isn't the one of the owner it signed for.
+    PublicKeyMismatch,
+    Locked,
+    /// The keys of an in-memory signer couldn't be read from the vault.
+    KeyStorage {
+        message: String,
+    },
+    /// The user declined to sign.
+    UserRejected {
+        message: String,
+    },
+    /// The signer didn't answer in time.
+    Timeout {
+        message: String,
+    },
+    /// Any other error thrown by a JS signer.
+    Js {
+        name: String,
+        message: String,
+    },
+    Unknown,
+}
+
+impl Error {
+    /// A name for the kind of error, as the `kind` property of errors surfaced to JS.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::MissingKey => "missingKey",
+            Error::SigningError => "signingError",
+            Error::PublicKeyParse => "publicKeyParse",
+            Error::JsConversion => "jsConversion",
+            Error::UnexpectedSignatureFormat => "unexpectedSignatureFormat",
+            Error::InvalidAccountOwnerType => "invalidAccountOwnerType",
+            Error::PublicKeyMismatch => "publicKeyMismatch",
+            Error::Locked => "locked",
+            Error::KeyStorage { .. } => "keyStorage",
+            Error::UserRejected { .. } => "userRejected",
+            Error::Timeout { .. } => "timeout",
+            Error::Js { .. } => "js",
+            Error::Unknown => "unknown",
+        }
+    }
+
+    /// The name and message of the JS error this error was made from, if any.
+    #[must_use]
+    pub fn js_error(&self) -> Option<(&str, &str)> {
+        match self {
+            Error::UserRejected { message } => Some(("UserRejected", message)),
+            Error::Timeout { message } => Some(("Timeout", message)),
+            Error::Js { name, message } => Some((name, message)),
+            _ => None,
+        }
+    }
+
+    /// Finds the signer error that caused `error`, if any.
+    #[must_use]
+    pub fn find<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a Error> {
+        let mut error = Some(error);
+        while let Some(current) = error {
+            if let Some(signer_error) = current.downcast_ref::<Error>() {
+                return Some(signer_error);
+            }
+            error = current.source();
+        }
+        None
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -38,9 +158,19 @@ impl Display for Error {
             Error::InvalidAccountOwnerType => {
                 write!(
                     f,
-                    "Invalid account owner type provided. Expected AccountOwner::Address20"
+                    "The signer doesn't support the signature scheme of this account owner"
                 )
             }
+            Error::PublicKeyMismatch => {
+                write!(f, "The signer's public key doesn't match the owner")
+            }
+            Error::Locked => write!(f, "The wallet is locked"),
+            Error::KeyStorage { message } => {
+                write!(f, "The signer keys couldn't be read: {message}")
+            }
+            Error::UserRejected { message } => write!(f, "The user rejected signing: {message}"),
+            Error::Timeout { message } => write!(f, "The signer timed out: {message}"),
+            Error::Js { name, message } => write!(f, "The signer failed: {name}: {message}"),
             Error::Unknown => write!(f, "An unknown error occurred"),
         }
     }
@@ -48,14 +178,43 @@ impl Display for Error {
 
 impl From<JsValue> for Error {
     fn from(value: JsValue) -> Self {
-        match value.as_f64().and_then(num_traits::cast) {
-            Some(0u8) => Error::MissingKey,
-            Some(1) => Error::SigningError,
-            Some(2) => Error::PublicKeyParse,
-            Some(3) => Error::JsConversion,
-            Some(4) => Error::UnexpectedSignatureFormat,
-            Some(5) => Error::InvalidAccountOwnerType,
-            _ => Error::Unknown,
+        // Signers used to throw the numeric codes of `ErrorCode`.
+        if let Some(code) = value.as_f64() {
+            let code = match num_traits::cast::<f64, u8>(code) {
+                Some(0) => ErrorCode::MissingKey,
+                Some(1) => ErrorCode::SigningError,
+                Some(2) => ErrorCode::PublicKeyParse,
+                Some(3) => ErrorCode::JsConversion,
+                Some(4) => ErrorCode::UnexpectedSignatureFormat,
+                Some(5) => ErrorCode::InvalidAccountOwnerType,
+                Some(6) => ErrorCode::Locked,
+                _ => ErrorCode::Unknown,
+            };
+            return code.into();
+        }
+        if let Some(message) = value.as_string() {
+            return Error::Js {
+                name: "Error".to_string(),
+                message,
+            };
+        }
+        let property = |name: &str| js_sys::Reflect::get(&value, &name.into()).ok();
+        let string = |name: &str| property(name).and_then(|value| value.as_string());
+        let name = string("name").unwrap_or_else(|| "Error".to_string());
+        let message = string("message").unwrap_or_else(|| {
+            value
+                .dyn_ref::<js_sys::Object>()
+                .map(|object| String::from(object.to_string()))
+                .unwrap_or_default()
+        });
+        if USER_REJECTED_NAMES.contains(&name.as_str())
+            || property("code").and_then(|code| code.as_f64()) == Some(USER_REJECTED_CODE)
+        {
+            Error::UserRejected { message }
+        } else if TIMEOUT_NAMES.contains(&name.as_str()) {
+            Error::Timeout { message }
+        } else {
+            Error::Js { name, message }
         }
     }
 }
@@ -67,24 +226,163 @@ const _: &str = r#"import type { Signer } from '../signer/index.js';"#;
 
 #[wasm_bindgen]
 extern "C" {
+    /// A signer implemented in JS, following the `Signer` interface.
     #[wasm_bindgen(typescript_type = "Signer")]
-    pub type Signer;
+    pub type JsSigner;
 
     #[wasm_bindgen(catch, method)]
     async fn sign(
-        this: &Signer,
+        this: &JsSigner,
         owner: AccountOwner,
         value: Vec<u8>,
     ) -> Result<js_sys::JsString, JsValue>;
 
     #[wasm_bindgen(catch, method, js_name = "containsKey")]
-    async fn contains_key(this: &Signer, owner: AccountOwner) -> Result<JsValue, JsValue>;
+    async fn contains_key(this: &JsSigner, owner: AccountOwner) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, method, js_name = "getPublicKey")]
+    async fn public_key(this: &JsSigner, owner: AccountOwner) -> Result<js_sys::JsString, JsValue>;
+}
+
+impl JsSigner {
+    /// Whether the signer supports `scheme`, according to its optional
+    /// `supportedSchemes` method. Signers without it only support secp256k1. The
+    /// method must be synchronous: if it returns a promise, no scheme is supported.
+    fn supports(&self, scheme: KeyScheme) -> bool {
+        let Some(method) = js_sys::Reflect::get(self, &"supportedSchemes".into())
+            .ok()
+            .and_then(|method| method.dyn_into::<js_sys::Function>().ok())
+        else {
+            return scheme == KeyScheme::Secp256k1;
+        };
+        let Ok(schemes) = method.call0(self) else {
+            tracing::warn!("the signer's supportedSchemes method failed");
+            return false;
+        };
+        if schemes.is_instance_of::<js_sys::Promise>() {
+            tracing::warn!("the signer's supportedSchemes method must not be async");
+            return false;
+        }
+        serde_wasm_bindgen::from_value::<Vec<KeyScheme>>(schemes)
+            .is_ok_and(|schemes| schemes.contains(&scheme))
+    }
+
+    /// The signature scheme of the keys of `owner`, if it is supported.
+    fn scheme_for(&self, owner: &AccountOwner) -> Option<KeyScheme> {
+        let scheme = match owner {
+            AccountOwner::Address20(_) => KeyScheme::Secp256k1,
+            AccountOwner::Address32(_) => KeyScheme::Ed25519,
+            AccountOwner::Reserved(_) => return None,
+        };
+        self.supports(scheme).then_some(scheme)
+    }
+
+    /// Signs with the Ed25519 key of `owner`: the JS signer signs the 32 bytes of the
+    /// hash, and provides its public key, which must match `owner`.
+    async fn sign_ed25519(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Error> {
+        let signature = String::from(self.sign(*owner, value.as_bytes().0.to_vec()).await?);
+        let public_key = String::from(self.public_key(*owner).await?);
+        ed25519_signature(owner, signature, public_key)
+    }
+}
+
+/// Makes the EIP-191 signature of `owner`, an EVM address, from its hex encoding.
+fn evm_signature(owner: &AccountOwner, signature: &str) -> Result<AccountSignature, Error> {
+    let AccountOwner::Address20(address) = owner else {
+        return Err(Error::InvalidAccountOwnerType);
+    };
+    Ok(AccountSignature::EvmSecp256k1 {
+        signature: signature
+            .parse()
+            .map_err(|_| Error::UnexpectedSignatureFormat)?,
+        address: *address,
+    })
+}
+
+/// Makes the Ed25519 signature of `owner` from the hex encodings of the signature and
+/// of the public key, which must match `owner`.
+fn ed25519_signature(
+    owner: &AccountOwner,
+    signature: String,
+    public_key: String,
+) -> Result<AccountSignature, Error> {
+    let signature: Ed25519Signature =
+        serde_json::from_value(signature.into()).map_err(|_| Error::UnexpectedSignatureFormat)?;
+    let public_key: Ed25519PublicKey =
+        serde_json::from_value(public_key.into()).map_err(|_| Error::PublicKeyParse)?;
+    if AccountOwner::from(public_key) != *owner {
+        return Err(Error::PublicKeyMismatch);
+    }
+    Ok(AccountSignature::Ed25519 {
+        signature,
+        public_key,
+    })
+}
+
+/// The signer used by a `Client`.
+#[derive(Clone)]
+pub enum Signer {
+    /// A JS object implementing the `Signer` interface.
+    Js(JsSigner),
+    /// Keys held in wasm memory.
+    InMemory(InMemorySigner),
+    /// Several signers, each signing for its own owners.
+    Composite(CompositeSigner),
+    /// A signer in another context, reached over a `MessagePort`.
+    #[cfg(feature = "web")]
+    Remote(RemoteSigner),
 }
 
 impl linera_base::crypto::Signer for Signer {
     type Error = Error;
 
     async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        match self {
+            // `JsSigner` has inherent methods of the same names.
+            Signer::Js(signer) => linera_base::crypto::Signer::contains_key(signer, owner).await,
+            Signer::InMemory(signer) => {
+                linera_base::crypto::Signer::contains_key(signer, owner).await
+            }
+            Signer::Composite(signer) => {
+                linera_base::crypto::Signer::contains_key(signer, owner).await
+            }
+            #[cfg(feature = "web")]
+            Signer::Remote(signer) => {
+                linera_base::crypto::Signer::contains_key(signer, owner).await
+            }
+        }
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Self::Error> {
+        match self {
+            Signer::Js(signer) => linera_base::crypto::Signer::sign(signer, owner, value).await,
+            Signer::InMemory(signer) => {
+                linera_base::crypto::Signer::sign(signer, owner, value).await
+            }
+            Signer::Composite(signer) => {
+                linera_base::crypto::Signer::sign(signer, owner, value).await
+            }
+            #[cfg(feature = "web")]
+            Signer::Remote(signer) => linera_base::crypto::Signer::sign(signer, owner, value).await,
+        }
+    }
+}
+
+impl linera_base::crypto::Signer for JsSigner {
+    type Error = Error;
+
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        if self.scheme_for(owner).is_none() {
+            return Ok(false);
+        }
         Ok(self.contains_key(*owner).await?.is_truthy())
     }
 
@@ -93,21 +391,18 @@ impl linera_base::crypto::Signer for Signer {
         owner: &AccountOwner,
         value: &CryptoHash,
     ) -> Result<AccountSignature, Self::Error> {
-        Ok(AccountSignature::EvmSecp256k1 {
-            signature: String::from(
-                self
-                    // Pass CryptoHash without serializing as that adds bytes
-                    // to the serialized value which we don't want for signing.
-                    .sign(*owner, value.as_bytes().0.to_vec())
-                    .await?,
-            )
-            .parse()
-            .map_err(|_| Error::UnexpectedSignatureFormat)?,
-            address: if let AccountOwner::Address20(address) = owner {
-                *address
-            } else {
-                return Err(Error::InvalidAccountOwnerType);
-            },
-        })
+        match self.scheme_for(owner) {
+            None => return Err(Error::InvalidAccountOwnerType),
+            Some(KeyScheme::Ed25519) => return self.sign_ed25519(owner, value).await,
+            Some(KeyScheme::Secp256k1) => {}
+        }
+        let signature = String::from(
+            self
+                // Pass CryptoHash without serializing as that adds bytes
+                // to the serialized value which we don't want for signing.
+                .sign(*owner, value.as_bytes().0.to_vec())
+                .await?,
+        );
+        evm_signature(owner, &signature)
     }
 }
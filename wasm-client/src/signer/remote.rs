@@ -0,0 +1,454 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A signer forwarding requests over a `MessagePort` to another context, such as a
+//! popup, another extension or a test harness, which holds the keys.
+//!
+//! Requests are JSON-RPC 2.0 messages, whose parameters carry the protocol
+//! [`VERSION`]:
+//!
+//! - `linera_containsKey`, with params `{ version, owner }`, answered with a boolean;
+//! - `linera_sign`, with params `{ version, owner, value }` where `value` is the hex of
+//!   the 32 bytes to sign, answered with `{ signature }`, the hex signature: EIP-191
+//!   for EVM addresses, or Ed25519 for 32-byte owners, in which case the answer also
+//!   has `publicKey`, the hex public key.
+//!
+//! If no answer comes within the timeout, or the request is dropped, the signer sends
+//! the notification `linera_cancel`, with params `{ version, id }`, and ignores any
+//! later answer. Errors answered with the EIP-1193 code 4001 are reported as the user
+//! rejecting the request, and those with code [`TIMEOUT_CODE`] as timeouts.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    time::Duration,
+};
+
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+};
+use linera_base::{
+    crypto::{AccountSignature, CryptoHash},
+    identifiers::AccountOwner,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use super::{ed25519_signature, evm_signature, Error};
+use crate::runtime;
+
+/// The version of the protocol.
+pub const VERSION: u32 = 1;
+
+/// The error code of requests rejected by the user, as in EIP-1193.
+pub const USER_REJECTED_CODE: i64 = 4001;
+/// The error code of requests the remote signer gave up on. It is in the range of
+/// JSON-RPC server errors, outside of the codes EIP-1193 and EIP-1474 assign.
+pub const TIMEOUT_CODE: i64 = -32090;
+
+const DEFAULT_TIMEOUT_MS: u32 = 60_000;
+
+/// Options of a `RemoteSigner`.
+#[derive(Default, Deserialize, tsify::Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSignerOptions {
+    /// How long to wait for an answer, in milliseconds. Defaults to one minute.
+    #[serde(default)]
+    pub timeout_ms: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    jsonrpc: String,
+    id: u64,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignResult {
+    signature: String,
+    #[serde(default)]
+    public_key: Option<String>,
+}
+
+type Pending = RefCell<HashMap<u64, oneshot::Sender<Result<Value, Error>>>>;
+
+struct Inner {
+    port: web_sys::MessagePort,
+    timeout: Duration,
+    next_id: Cell<u64>,
+    pending: Rc<Pending>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.port.set_onmessage(None);
+    }
+}
+
+/// Removes a request from the pending ones when dropped, sending `linera_cancel` if it
+/// wasn't answered.
+struct RequestGuard<'a> {
+    inner: &'a Inner,
+    id: u64,
+}
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        if self.inner.pending.borrow_mut().remove(&self.id).is_some() {
+            self.inner.send_cancel(self.id);
+        }
+    }
+}
+
+impl Inner {
+    /// Tells the remote signer that request `id` was given up on.
+    fn send_cancel(&self, id: u64) {
+        let cancel = json!({
+            "jsonrpc": "2.0",
+            "method": "linera_cancel",
+            "params": { "version": VERSION, "id": id },
+        });
+        if let Err(error) = self.post(&cancel) {
+            tracing::warn!("failed to cancel remote signer request: {error}");
+        }
+    }
+
+    fn post(&self, message: &Value) -> Result<(), Error> {
+        let message = message
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .map_err(|_| Error::JsConversion)?;
+        self.port.post_message(&message).map_err(Error::from)
+    }
+
+    /// Sends a request and waits for its result.
+    async fn request(&self, method: &str, mut params: Value) -> Result<Value, Error> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        params["version"] = VERSION.into();
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.borrow_mut().insert(id, sender);
+        let _guard = RequestGuard { inner: self, id };
+        self.post(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+
+        // Dropping the timeout when the answer comes first clears its timer.
+        let timeout = Box::pin(runtime::sleep(self.timeout));
+        match future::select(receiver, timeout).await {
+            Either::Left((Ok(result), _)) => result,
+            // The sender is only dropped when all requests are cancelled.
+            Either::Left((Err(oneshot::Canceled), _)) => Err(Error::Js {
+                name: "AbortError".to_string(),
+                message: format!("The {method} request was cancelled"),
+            }),
+            Either::Right((Err(error), _)) => Err(Error::Js {
+                name: "Error".to_string(),
+                message: error.message(),
+            }),
+            Either::Right((Ok(()), _)) => Err(Error::Timeout {
+                message: format!(
+                    "No answer to {method} within {} ms",
+                    self.timeout.as_millis()
+                ),
+            }),
+        }
+    }
+}
+
+/// Delivers an answer to the request it is for.
+fn on_message(pending: &Pending, data: JsValue) {
+    let Ok(response) = serde_wasm_bindgen::from_value::<Response>(data) else {
+        tracing::debug!("ignoring a message that isn't a JSON-RPC response");
+        return;
+    };
+    if response.jsonrpc != "2.0" {
+        tracing::debug!("ignoring a response of JSON-RPC {}", response.jsonrpc);
+        return;
+    }
+    let Some(sender) = pending.borrow_mut().remove(&response.id) else {
+        tracing::debug!("ignoring an answer to unknown request {}", response.id);
+        return;
+    };
+    let result = match (response.result, response.error) {
+        (_, Some(error)) => Err(match error.code {
+            USER_REJECTED_CODE => Error::UserRejected {
+                message: error.message,
+            },
+            TIMEOUT_CODE => Error::Timeout {
+                message: error.message,
+            },
+            code => Error::Js {
+                name: error
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.get("name"))
+                    .and_then(Value::as_str)
+                    .map_or_else(|| format!("RpcError {code}"), str::to_string),
+                message: error.message,
+            },
+        }),
+        (Some(result), None) => Ok(result),
+        (None, None) => Err(Error::UnexpectedSignatureFormat),
+    };
+    // The request may have been given up on in the meantime.
+    let _ = sender.send(result);
+}
+
+/// A signer forwarding requests over a `MessagePort`, using the protocol described in
+/// the `linera-web` documentation of `signer::remote`.
+///
+/// Clones share the same port and requests.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct RemoteSigner {
+    inner: Rc<Inner>,
+}
+
+#[wasm_bindgen]
+impl RemoteSigner {
+    /// Creates a signer sending its requests over `port`, and starts listening to
+    /// answers on it.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new(port: web_sys::MessagePort, options: Option<RemoteSignerOptions>) -> RemoteSigner {
+        let options = options.unwrap_or_default();
+        let pending = Rc::<Pending>::default();
+        let on_message = {
+            let pending = Rc::downgrade(&pending);
+            Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+                if let Some(pending) = pending.upgrade() {
+                    on_message(&pending, event.data());
+                }
+            })
+        };
+        port.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        port.start();
+        RemoteSigner {
+            inner: Rc::new(Inner {
+                port,
+                timeout: Duration::from_millis(
+                    options.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS).into(),
+                ),
+                next_id: Cell::new(0),
+                pending,
+                _on_message: on_message,
+            }),
+        }
+    }
+
+    /// Cancels every request waiting for an answer: they fail with an `AbortError`, and
+    /// the remote signer is sent `linera_cancel` for each of them.
+    pub fn cancel(&self) {
+        // Dropping the senders wakes the requests up.
+        let ids = self
+            .inner
+            .pending
+            .borrow_mut()
+            .drain()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            self.inner.send_cancel(id);
+        }
+    }
+
+    /// Cancels every request waiting for an answer, and closes the port.
+    pub fn close(&self) {
+        self.cancel();
+        self.inner.port.close();
+    }
+}
+
+impl linera_base::crypto::Signer for RemoteSigner {
+    type Error = Error;
+
+    async fn contains_key(&self, owner: &AccountOwner) -> Result<bool, Self::Error> {
+        let result = self
+            .inner
+            .request("linera_containsKey", json!({ "owner": owner }))
+            .await?;
+        result.as_bool().ok_or(Error::JsConversion)
+    }
+
+    async fn sign(
+        &self,
+        owner: &AccountOwner,
+        value: &CryptoHash,
+    ) -> Result<AccountSignature, Self::Error> {
+        if matches!(owner, AccountOwner::Reserved(_)) {
+            return Err(Error::InvalidAccountOwnerType);
+        }
+        let result = self
+            .inner
+            .request(
+                "linera_sign",
+                json!({ "owner": owner, "value": hex::encode(value.as_bytes().0) }),
+            )
+            .await?;
+        let SignResult {
+            signature,
+            public_key,
+        } = serde_json::from_value(result).map_err(|_| Error::UnexpectedSignatureFormat)?;
+        match owner {
+            AccountOwner::Address32(_) => {
+                ed25519_signature(owner, signature, public_key.ok_or(Error::PublicKeyParse)?)
+            }
+            _ => evm_signature(owner, &signature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linera_base::crypto::Signer as _;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    const OWNER: AccountOwner = AccountOwner::Address20([1; 20]);
+
+    /// The other end of a `MessageChannel`, answering requests with `answer` and
+    /// recording the methods it is sent.
+    struct Mock {
+        signer: RemoteSigner,
+        methods: Rc<RefCell<Vec<String>>>,
+        _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    }
+
+    fn mock(answer: impl Fn(&Value) -> Option<Value> + 'static, timeout_ms: u32) -> Mock {
+        let channel = web_sys::MessageChannel::new().unwrap();
+        let port = channel.port2();
+        let methods = Rc::<RefCell<Vec<String>>>::default();
+        let on_message = {
+            let (port, methods) = (port.clone(), methods.clone());
+            Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+                let request: Value = serde_wasm_bindgen::from_value(event.data()).unwrap();
+                methods
+                    .borrow_mut()
+                    .push(request["method"].as_str().unwrap().to_string());
+                if let Some(response) = answer(&request) {
+                    let response = response
+                        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                        .unwrap();
+                    port.post_message(&response).unwrap();
+                }
+            })
+        };
+        port.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        port.start();
+        let options = RemoteSignerOptions {
+            timeout_ms: Some(timeout_ms),
+        };
+        Mock {
+            signer: RemoteSigner::new(channel.port1(), Some(options)),
+            methods,
+            _on_message: on_message,
+        }
+    }
+
+    /// Lets the messages in flight be delivered.
+    async fn settle() {
+        runtime::sleep(Duration::from_millis(20)).await.unwrap();
+    }
+
+    #[wasm_bindgen_test]
+    async fn answers_are_delivered() {
+        let mock = mock(
+            |request| Some(json!({ "jsonrpc": "2.0", "id": request["id"], "result": true })),
+            1_000,
+        );
+        assert!(mock.signer.contains_key(&OWNER).await.unwrap());
+        assert_eq!(*mock.methods.borrow(), ["linera_containsKey"]);
+    }
+
+    #[wasm_bindgen_test]
+    async fn rejections_are_reported() {
+        let mock = mock(
+            |request| {
+                Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": USER_REJECTED_CODE, "message": "no" },
+                }))
+            },
+            1_000,
+        );
+        let error = mock.signer.contains_key(&OWNER).await.unwrap_err();
+        assert_eq!(
+            error,
+            Error::UserRejected {
+                message: "no".to_string()
+            }
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn remote_timeouts_are_reported() {
+        let answer = |code: i64| {
+            move |request: &Value| {
+                Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": code, "message": "too late" },
+                }))
+            }
+        };
+        let timed_out = mock(answer(TIMEOUT_CODE), 1_000);
+        let error = timed_out.signer.contains_key(&OWNER).await.unwrap_err();
+        assert!(matches!(error, Error::Timeout { .. }));
+        // EIP-1193's "Disconnected" isn't a timeout.
+        let disconnected = mock(answer(4900), 1_000);
+        let error = disconnected.signer.contains_key(&OWNER).await.unwrap_err();
+        assert!(matches!(error, Error::Js { name, .. } if name == "RpcError 4900"));
+    }
+
+    #[wasm_bindgen_test]
+    async fn timed_out_requests_are_cancelled() {
+        let mock = mock(|_| None, 10);
+        let error = mock.signer.contains_key(&OWNER).await.unwrap_err();
+        assert!(matches!(error, Error::Timeout { .. }));
+        settle().await;
+        assert_eq!(
+            *mock.methods.borrow(),
+            ["linera_containsKey", "linera_cancel"]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancel_sends_linera_cancel() {
+        let mock = mock(|_| None, 60_000);
+        let cancel = async {
+            settle().await;
+            mock.signer.cancel();
+        };
+        let (result, ()) = futures::join!(mock.signer.contains_key(&OWNER), cancel);
+        assert!(matches!(result, Err(Error::Js { name, .. }) if name == "AbortError"));
+        settle().await;
+        assert_eq!(
+            *mock.methods.borrow(),
+            ["linera_containsKey", "linera_cancel"]
+        );
+    }
+}
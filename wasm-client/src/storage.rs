@@ -1,23 +1,260 @@
 // Copyright (c) Zefchain Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-// TODO(#4637): convert to IndexedDbStore once we refactor Context
-pub type Storage =
-    linera_storage::DbStorage<linera_views::memory::MemoryDatabase, linera_storage::WallClock>;
+//! Persistent storage for chain state (certificates, blobs and execution state), so that
+//! chains don't have to be re-downloaded and re-executed on every start.
+//!
+//! Native builds keep the chain state in memory, so it only lasts as long as the
+//! process.
 
-/// Create and return the storage implementation.
+#[cfg(feature = "web")]
+use linera_views::indexed_db::{IndexedDbDatabase as Database, IndexedDbStoreConfig};
+#[cfg(not(feature = "web"))]
+use linera_views::memory::{MemoryDatabase as Database, MemoryStoreConfig};
+use linera_views::{
+    store::{KeyValueDatabase as _, ReadableKeyValueStore as _},
+    ViewError,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    profile::{self, Profile},
+    utils::WalletStorage as _,
+    wallet::schema,
+    JsError, JsResult,
+};
+
+pub type Storage = linera_storage::DbStorage<Database, linera_storage::WallClock>;
+
+/// The namespace holding the chain state of the default profile, distinct from the
+/// wallet itself.
+pub(crate) const NAMESPACE: &str = "linera_chain_state";
+/// The wallet field holding the chain storage quota, in bytes.
+const QUOTA_FIELD: &str = "chain_storage_quota";
+/// How many values are read at once when measuring the chain state.
+const USAGE_BATCH_SIZE: usize = 100;
+
+#[cfg(feature = "web")]
+fn config() -> IndexedDbStoreConfig {
+    IndexedDbStoreConfig {
+        max_stream_queries: 1,
+    }
+}
+
+#[cfg(not(feature = "web"))]
+fn config() -> MemoryStoreConfig {
+    MemoryStoreConfig {
+        max_stream_queries: 1,
+        kill_on_drop: false,
+    }
+}
+
+/// Create and return the storage implementation for the chain state of `profile`.
+///
+/// If a quota is set and the chain state of `profile` takes more than that, it is
+/// cleared first and will be synchronized again from the validators.
 ///
 /// # Errors
 /// If the storage can't be initialized.
-pub async fn get_storage() -> Result<Storage, linera_views::ViewError> {
+pub async fn get_storage(profile: &Profile) -> Result<Storage, JsError> {
+    enforce_quota(profile).await?;
     Ok(linera_storage::DbStorage::maybe_create_and_connect(
-        &linera_views::memory::MemoryStoreConfig {
-            max_stream_queries: 1,
-            kill_on_drop: false,
-        },
-        "linera",
+        &config(),
+        &profile.chain_state_namespace(),
         Some(linera_execution::WasmRuntime::Wasmer),
     )
-    .await?
+    .await
+    .map_err(ViewError::from)?
     .with_allow_application_logs(true))
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "web")]
+async fn enforce_quota(profile: &Profile) -> JsResult<()> {
+    let Some(quota) = read_quota(profile).await? else {
+        return Ok(());
+    };
+    let namespace = profile.chain_state_namespace();
+    let usage = namespace_usage(&namespace).await?;
+    if usage > quota {
+        tracing::warn!(
+            "chain state ({usage} bytes) exceeds the quota ({quota} bytes): clearing it"
+        );
+        clear_namespace(&namespace).await?;
+    }
+    Ok(())
+}
+
+/// Native chain state is in memory and isn't subject to the quota.
+#[cfg(not(feature = "web"))]
+async fn enforce_quota(_profile: &Profile) -> JsResult<()> {
+    Ok(())
+}
+
+async fn read_quota(profile: &Profile) -> JsResult<Option<f64>> {
+    Ok(schema::storage_for(profile)
+        .read_field(QUOTA_FIELD)
+        .await?
+        .and_then(|quota| quota.as_f64()))
+}
+
+/// Returns the number of bytes of keys and values stored under `namespace`.
+///
+/// This reads every partition of the namespace, so it is only done when a quota is
+/// set or the usage is asked for. Values are read [`USAGE_BATCH_SIZE`] at a time, so
+/// that the whole chain state is never held in memory at once.
+///
+/// # Errors
+/// If storage is inaccessible.
+// Chain state is far below the 2^53 bytes an `f64` counts exactly.
+#[allow(clippy::cast_precision_loss)]
+async fn namespace_usage(namespace: &str) -> JsResult<f64> {
+    let config = config();
+    if !Database::exists(&config, namespace)
+        .await
+        .map_err(ViewError::from)?
+    {
+        return Ok(0.0);
+    }
+    let database = Database::connect(&config, namespace)
+        .await
+        .map_err(ViewError::from)?;
+    let mut usage = 0;
+    for root_key in Database::list_root_keys(&config, namespace)
+        .await
+        .map_err(ViewError::from)?
+    {
+        let store = database.open_shared(&root_key).map_err(ViewError::from)?;
+        let keys = store
+            .find_keys_by_prefix(&[])
+            .await
+            .map_err(ViewError::from)?;
+        for keys in keys.chunks(USAGE_BATCH_SIZE) {
+            usage += keys.iter().map(Vec::len).sum::<usize>();
+            usage += store
+                .read_multi_values_bytes(keys.to_vec())
+                .await
+                .map_err(ViewError::from)?
+                .iter()
+                .flatten()
+                .map(Vec::len)
+                .sum::<usize>();
+        }
+    }
+    Ok(usage as f64)
+}
+
+/// Deletes the chain state stored under `namespace`, if any.
+///
+/// # Errors
+/// If storage is inaccessible.
+pub(crate) async fn clear_namespace(namespace: &str) -> JsResult<()> {
+    if Database::exists(&config(), namespace)
+        .await
+        .map_err(ViewError::from)?
+    {
+        Database::delete(&config(), namespace)
+            .await
+            .map_err(ViewError::from)?;
+    }
+    Ok(())
+}
+
+/// Management of the persisted chain state, exposed to JS.
+///
+/// The chain state is a cache: clearing it loses nothing, but the chains in the wallet
+/// will be synchronized again from the validators the next time a client starts.
+#[wasm_bindgen]
+pub struct ChainStorage;
+
+#[wasm_bindgen]
+impl ChainStorage {
+    /// Deletes the persisted chain state of the selected profile. This must not be
+    /// called while a `Client` is running on it.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub async fn clear() -> JsResult<()> {
+        clear_namespace(&profile::current().await?.chain_state_namespace()).await
+    }
+
+    /// Returns the number of bytes of chain state of the selected profile, which is
+    /// what the quota applies to.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub async fn usage() -> JsResult<f64> {
+        namespace_usage(&profile::current().await?.chain_state_namespace()).await
+    }
+
+    /// Returns the storage quota of the selected profile, past which its chain state
+    /// is cleared on startup, if any.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub async fn quota() -> JsResult<Option<f64>> {
+        read_quota(&profile::current().await?).await
+    }
+
+    /// Sets the storage quota of the selected profile in bytes, checked whenever a
+    /// `Client` starts, or removes it if `bytes` is `undefined`.
+    ///
+    /// # Errors
+    /// If `bytes` isn't a finite, non-negative number, or if storage is inaccessible.
+    #[wasm_bindgen(js_name = setQuota)]
+    pub async fn set_quota(bytes: Option<f64>) -> JsResult<()> {
+        if let Some(bytes) = bytes.filter(|bytes| !bytes.is_finite() || *bytes < 0.0) {
+            return Err(JsError::new(&format!("Invalid storage quota {bytes}")));
+        }
+        let storage = schema::storage().await?;
+        match bytes {
+            Some(bytes) => storage.write_field(QUOTA_FIELD, bytes.into()).await?,
+            None => storage.remove_field(QUOTA_FIELD).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "web")))]
+mod tests {
+    use futures::executor::block_on;
+    use linera_views::{batch::Batch, store::WritableKeyValueStore as _};
+
+    use super::*;
+    use crate::wallet::FileStorage;
+
+    // The sizes are small integers, which an `f64` holds exactly.
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn usage_counts_keys_and_values() {
+        block_on(async {
+            let namespace = "usage_test";
+            let database = Database::maybe_create_and_connect(&config(), namespace)
+                .await
+                .unwrap();
+            for root_key in [[1], [2]] {
+                let store = database.open_exclusive(&root_key).unwrap();
+                let mut batch = Batch::new();
+                batch.put_key_value_bytes(vec![1, 2], vec![0; 10]);
+                batch.put_key_value_bytes(vec![3], vec![0; 5]);
+                store.write_batch(batch).await.unwrap();
+            }
+            assert_eq!(namespace_usage(namespace).await.unwrap(), 36.0);
+            clear_namespace(namespace).await.unwrap();
+            assert_eq!(namespace_usage(namespace).await.unwrap(), 0.0);
+        });
+    }
+
+    #[test]
+    fn invalid_quotas_are_rejected() {
+        FileStorage::use_test_data_dir();
+        block_on(async {
+            for bytes in [-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                assert!(ChainStorage::set_quota(Some(bytes)).await.is_err());
+            }
+            ChainStorage::set_quota(Some(1024.0)).await.unwrap();
+            assert_eq!(ChainStorage::quota().await.unwrap(), Some(1024.0));
+            ChainStorage::set_quota(None).await.unwrap();
+            assert_eq!(ChainStorage::quota().await.unwrap(), None);
+        });
+    }
+}
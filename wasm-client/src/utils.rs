@@ -1,29 +1,21 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
 use async_trait::async_trait;
-use rexie::{ObjectStore, Rexie, TransactionMode};
-use wasm_bindgen::{prelude::wasm_bindgen, JsError, JsValue};
+use wasm_bindgen::prelude::wasm_bindgen;
 
-/**
- * Methods to Encrypt and Decrypt the wallet
-*/
-// These basic encryption/decryption functions are no longer needed
-// as we will be storing individual fields directly.
-/*
-fn _encrypt_wallet(_wallet: &str, _key: &str) -> Result<String, JsError> {
-    // Encrypt the wallet using AES
-    todo!()
-}
-
-fn _decrypt_wallet(_wallet_hash: &str, _key: &str) -> Result<String, JsError> {
-    // Decrypt the wallet using AES
-    todo!()
-}
-*/
+use crate::{
+    crypto,
+    profile::{self, Profile},
+    session,
+    wallet::schema,
+    JsError, JsResult,
+};
 
 /**
  * Secret Vault API (built on IndexedDB helpers above)
  *
  * These helpers namespace keys and reuse `persistent_wallet` to store and retrieve
- * arbitrary `JsValue`s that represent secrets or sensitive data.
+ * arbitrary JSON values that represent secrets or sensitive data.
  */
 
 /// Prefix used to namespace all keys stored by the Secret Vault within IndexedDB
@@ -33,6 +25,11 @@ fn vault_key(field: &str) -> String {
     format!("{}{}", VAULT_PREFIX, field)
 }
 
+thread_local! {
+    /// The profiles whose vault was already moved out of its legacy database.
+    static MOVED_VAULTS: RefCell<HashSet<String>> = RefCell::default();
+}
+
 #[wasm_bindgen(js_name = "Secret")]
 pub struct SecretVault;
 
@@ -43,133 +40,310 @@ impl SecretVault {
     pub fn new() -> SecretVault {
         SecretVault
     }
+}
 
+impl SecretVault {
     /// Stores a secret value under the provided field name.
-    /// Returns a JS Promise that resolves when the value has been written.
-    #[wasm_bindgen]
-    pub async fn set(&self, field: String, value: JsValue) -> Result<(), JsError> {
-        let db = IndexedDbStorage::new("linera_store", "vault", 1);
-        db.write_field(&vault_key(&field), value).await
-    }
-
-    /// Retrieves a secret value for the provided field name.
-    /// Returns `undefined` if the key is not present.
-    #[wasm_bindgen]
-    pub async fn get(&self, field: String) -> Result<JsValue, JsError> {
-        let db = IndexedDbStorage::new("linera_store", "vault", 1);
-        match db.read_field(&vault_key(&field)).await {
-            Ok(Some(v)) => Ok(v),
-            Ok(None) => Ok(JsValue::UNDEFINED),
-            Err(e) => Err(e),
+    ///
+    /// Secrets are encrypted under the session key if the wallet is password-protected.
+    ///
+    /// # Errors
+    /// If the wallet is locked or storage is inaccessible.
+    pub async fn set(&self, field: &str, value: serde_json::Value) -> JsResult<()> {
+        session::ensure_unlocked().await?;
+        let db = Self::storage().await?;
+        Ok(db.write_field(&vault_key(field), value).await?)
+    }
+
+    /// Retrieves the secret value for the provided field name, or `None` if the key
+    /// is not present.
+    ///
+    /// # Errors
+    /// If the wallet is locked or storage is inaccessible.
+    pub async fn get(&self, field: &str) -> JsResult<Option<serde_json::Value>> {
+        session::ensure_unlocked().await?;
+        let db = Self::storage().await?;
+        Ok(db.read_field(&vault_key(field)).await?)
+    }
+
+    /// Returns a handle on the (unencrypted) vault of `profile`, a store of its wallet
+    /// database.
+    ///
+    /// Secrets used to be kept in a database of their own: the first time the vault
+    /// of a profile is opened, they are moved from there.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub(crate) async fn open(profile: &Profile) -> JsResult<PlatformStorage> {
+        let vault = schema::vault_storage_for(profile);
+        if !MOVED_VAULTS.with_borrow(|moved| moved.contains(&profile.id)) {
+            Self::legacy_storage_for(profile).move_into(&vault).await?;
+            MOVED_VAULTS.with_borrow_mut(|moved| moved.insert(profile.id.clone()));
         }
+        Ok(vault)
+    }
+
+    /// Returns a handle on the database that held the vault of `profile` before it
+    /// moved into the wallet database.
+    pub(crate) fn legacy_storage_for(profile: &Profile) -> PlatformStorage {
+        open_store(&profile.vault_db(), "vault", 1)
+    }
+
+    /// Returns a handle on the vault of the selected profile, encrypted under the
+    /// session key if the wallet is password-protected.
+    pub(crate) async fn storage() -> JsResult<Rc<dyn WalletStorage>> {
+        let profile = profile::current().await?;
+        let db = Self::open(&profile).await?;
+        if session::is_protected_for(&profile).await? {
+            encrypted(&db, session::KeySource::Session(profile.id))
+        } else {
+            Ok(Rc::new(db))
+        }
+    }
+
+    /// Reads every secret of `profile`, decrypting them under the current session key
+    /// if any.
+    pub(crate) async fn read_all(profile: &Profile) -> JsResult<Vec<(String, serde_json::Value)>> {
+        let db = Self::open(profile).await?;
+        let db: Rc<dyn WalletStorage> = if session::is_protected_for(profile).await? {
+            encrypted(&db, session::KeySource::Session(profile.id.clone()))?
+        } else {
+            Rc::new(db)
+        };
+        Ok(db.read_all_fields().await?)
     }
 }
 
-#[async_trait(?Send)]
-pub trait WalletStorage {
-    async fn read_field(&self, key: &str) -> Result<Option<JsValue>, JsError>;
-    async fn write_field(&self, key: &str, value: JsValue) -> Result<(), JsError>;
-    async fn write_fields(&self, fields: Vec<(String, JsValue)>) -> Result<(), JsError>;
+/// An error reading or writing a [`WalletStorage`].
+#[derive(Debug, Clone)]
+pub struct StorageError(String);
+
+impl StorageError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
 }
 
-#[derive(Clone)]
-pub struct IndexedDbStorage {
-    db_name: String,
-    store_name: String,
-    version: u32,
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
-impl IndexedDbStorage {
-    pub fn new(db_name: impl Into<String>, store_name: impl Into<String>, version: u32) -> Self {
-        Self {
-            db_name: db_name.into(),
-            store_name: store_name.into(),
-            version,
-        }
+impl std::error::Error for StorageError {}
+
+impl From<crypto::Error> for StorageError {
+    fn from(error: crypto::Error) -> Self {
+        Self(error.to_string())
     }
+}
 
-    async fn get_db(&self) -> Result<Rexie, JsError> {
-        let db = Rexie::builder(&self.db_name)
-            .version(self.version)
-            .add_object_store(ObjectStore::new(&self.store_name))
-            .build()
-            .await
-            .map_err(|e| JsError::new(&format!("Failed to open IndexedDB: {:?}", e)))?;
-        Ok(db)
+/// Returns the key to seal or open `field` with in a store encrypted under
+/// `encryption`, or `None` if the field is stored as is.
+///
+/// # Errors
+/// If the key is the session's and the session is locked.
+pub(crate) fn field_key(
+    encryption: Option<&session::KeySource>,
+    field: &str,
+) -> Result<Option<Rc<crypto::WalletKey>>, StorageError> {
+    match encryption {
+        Some(source) if field != crypto::KDF_FIELD => source
+            .key()
+            .map(Some)
+            .map_err(|_| StorageError::new("The wallet is locked")),
+        _ => Ok(None),
     }
 }
 
+/// Seals the JSON encoding of `value` under `key`, bound to `field`.
+///
+/// # Errors
+/// If the value can't be serialized or sealed.
+pub(crate) fn seal_value(
+    key: &crypto::WalletKey,
+    field: &str,
+    value: &serde_json::Value,
+) -> Result<Vec<u8>, StorageError> {
+    let plaintext = zeroize::Zeroizing::new(
+        serde_json::to_vec(value)
+            .map_err(|e| StorageError::new(format!("Failed to serialize '{field}': {e}")))?,
+    );
+    Ok(key.seal(field, &plaintext)?)
+}
+
+/// Opens a value sealed by [`seal_value`] under the same key and field.
+///
+/// # Errors
+/// If the value doesn't authenticate or isn't valid JSON.
+pub(crate) fn open_value(
+    key: &crypto::WalletKey,
+    field: &str,
+    sealed: &[u8],
+) -> Result<serde_json::Value, StorageError> {
+    let plaintext = key.open(field, sealed)?;
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| StorageError::new(format!("Failed to deserialize '{field}': {e}")))
+}
+
+/// A key-value store holding one JSON value per field.
+///
+/// The wallet, the Secret vault and the profile registry are all stored this way.
+/// [`IndexedDbStorage`] is used in the browser; `wallet::MemoryStorage` and, outside
+/// of wasm, `wallet::FileStorage` let the same logic run elsewhere.
 #[async_trait(?Send)]
-impl WalletStorage for IndexedDbStorage {
-    async fn read_field(&self, key: &str) -> Result<Option<JsValue>, JsError> {
-        let db = self.get_db().await?;
+pub trait WalletStorage {
+    async fn read_field(&self, key: &str) -> Result<Option<serde_json::Value>, StorageError>;
 
-        let tx = db
-            .transaction(&[&self.store_name], TransactionMode::ReadOnly)
-            .map_err(|e| JsError::new(&format!("Failed to start transaction: {:?}", e)))?;
-        let store = tx
-            .store(&self.store_name)
-            .map_err(|e| JsError::new(&format!("Failed to open store: {:?}", e)))?;
+    /// Reads several fields at once, in a single transaction if the backend supports
+    /// it, so that they are consistent with each other.
+    async fn read_fields(
+        &self,
+        keys: &[&str],
+    ) -> Result<Vec<Option<serde_json::Value>>, StorageError> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.read_field(key).await?);
+        }
+        Ok(values)
+    }
 
-        let result = store
-            .get(JsValue::from_str(key))
-            .await
-            .map_err(|e| JsError::new(&format!("Failed to read '{}': {:?}", key, e)))?;
+    async fn write_field(&self, key: &str, value: serde_json::Value) -> Result<(), StorageError>;
+    async fn write_fields(
+        &self,
+        fields: Vec<(String, serde_json::Value)>,
+    ) -> Result<(), StorageError>;
+    async fn read_all_fields(&self) -> Result<Vec<(String, serde_json::Value)>, StorageError>;
 
-        // Wait for transaction to complete
-        tx.done()
-            .await
-            .map_err(|e| JsError::new(&format!("Transaction failed: {:?}", e)))?;
+    /// Removes `key` from the store, if it is there.
+    async fn remove_field(&self, key: &str) -> Result<(), StorageError>;
 
-        Ok(result)
+    /// Whether fields are encrypted at rest.
+    fn is_encrypted(&self) -> bool {
+        false
     }
 
-    async fn write_field(&self, key: &str, value: JsValue) -> Result<(), JsError> {
-        let db = self.get_db().await?;
+    /// Returns a handle on the same store that encrypts fields under the key of
+    /// `source`, if this backend supports encryption.
+    fn to_encrypted(&self, _source: session::KeySource) -> Option<Rc<dyn WalletStorage>> {
+        None
+    }
 
-        let tx = db
-            .transaction(&[&self.store_name], TransactionMode::ReadWrite)
-            .map_err(|e| JsError::new(&format!("Failed to start transaction: {:?}", e)))?;
-        let store = tx
-            .store(&self.store_name)
-            .map_err(|e| JsError::new(&format!("Failed to open store: {:?}", e)))?;
+    /// Returns this store as an IndexedDB object store, if it is one, so that it can
+    /// take part in transactions across stores.
+    #[cfg(feature = "web")]
+    fn as_indexed_db(&self) -> Option<&IndexedDbStorage> {
+        None
+    }
 
-        store
-            .put(&value, Some(&JsValue::from_str(key)))
-            .await
-            .map_err(|e| JsError::new(&format!("Failed to put '{}': {:?}", key, e)))?;
+    /// Reads the key derivation parameters, if this store holds an encrypted wallet.
+    ///
+    /// # Errors
+    /// If storage is inaccessible or the parameters are malformed.
+    async fn read_kdf_params(&self) -> Result<Option<crypto::KdfParams>, StorageError> {
+        let Some(value) = self.read_field(crypto::KDF_FIELD).await? else {
+            return Ok(None);
+        };
+        let json = value.as_str().ok_or(crypto::Error::MalformedParams)?;
+        Ok(Some(
+            serde_json::from_str(json).map_err(|_| crypto::Error::MalformedParams)?,
+        ))
+    }
+}
 
-        tx.done()
-            .await
-            .map_err(|e| JsError::new(&format!("Transaction commit failed: {:?}", e)))?;
+/// Encodes the key derivation parameters as a field value for [`crypto::KDF_FIELD`].
+///
+/// # Errors
+/// If the parameters can't be serialized.
+pub fn kdf_params_value(params: &crypto::KdfParams) -> Result<serde_json::Value, StorageError> {
+    let json = serde_json::to_string(params)
+        .map_err(|e| StorageError::new(format!("Failed to serialize KDF parameters: {e}")))?;
+    Ok(serde_json::Value::String(json))
+}
 
-        Ok(())
-    }
+#[cfg(feature = "web")]
+mod indexed_db;
 
-    async fn write_fields(&self, fields: Vec<(String, JsValue)>) -> Result<(), JsError> {
-        let db = self.get_db().await?;
+#[cfg(feature = "web")]
+pub use indexed_db::IndexedDbStorage;
 
-        let tx = db
-            .transaction(&[&self.store_name], TransactionMode::ReadWrite)
-            .map_err(|e| JsError::new(&format!("Failed to start transaction: {:?}", e)))?;
-        let store = tx
-            .store(&self.store_name)
-            .map_err(|e| JsError::new(&format!("Failed to open store: {:?}", e)))?;
+/// The storage backend of the platform.
+#[cfg(feature = "web")]
+pub type PlatformStorage = IndexedDbStorage;
+/// The storage backend of the platform.
+#[cfg(not(feature = "web"))]
+pub type PlatformStorage = crate::wallet::FileStorage;
 
-        let iter = fields.into_iter().map(|(key, value)| {
-            let key_js = JsValue::from_str(&key);
-            (value, Some(key_js))
-        });
+/// Opens the object store `store_name`, the only one of the database `db_name`.
+pub fn open_store(db_name: &str, store_name: &str, version: u32) -> PlatformStorage {
+    open_store_in(db_name, &[store_name], store_name, version)
+}
 
-        store
-            .put_all(iter)
-            .await
-            .map_err(|e| JsError::new(&format!("Failed to write fields: {:?}", e)))?;
+/// Opens the object store `store_name` of the database `db_name`, whose object stores
+/// are `stores`: an IndexedDB object store in the browser, and a JSON file under the
+/// data directory natively (see `wallet::FileStorage::data_dir`).
+#[cfg(feature = "web")]
+pub fn open_store_in(
+    db_name: &str,
+    stores: &[&str],
+    store_name: &str,
+    version: u32,
+) -> PlatformStorage {
+    IndexedDbStorage::in_database(db_name, stores, store_name, version)
+}
 
-        tx.done()
-            .await
-            .map_err(|e| JsError::new(&format!("Transaction commit failed: {:?}", e)))?;
+/// Opens the object store `store_name` of the database `db_name`, whose object stores
+/// are `stores`: an IndexedDB object store in the browser, and a JSON file under the
+/// data directory natively (see `wallet::FileStorage::data_dir`).
+#[cfg(not(feature = "web"))]
+pub fn open_store_in(
+    db_name: &str,
+    _stores: &[&str],
+    store_name: &str,
+    _version: u32,
+) -> PlatformStorage {
+    crate::wallet::FileStorage::new(
+        crate::wallet::FileStorage::data_dir()
+            .join(db_name)
+            .join(format!("{store_name}.json")),
+    )
+}
 
-        Ok(())
+/// Writes fields to several stores at once.
+///
+/// IndexedDB stores of the same database are written in a single transaction, so
+/// that either every write is applied or none is. Other backends apply the writes
+/// one store at a time.
+///
+/// # Errors
+/// If storage is inaccessible, or if IndexedDB stores of different databases are
+/// given.
+pub async fn write_atomically(
+    writes: Vec<(&dyn WalletStorage, Vec<(String, serde_json::Value)>)>,
+) -> Result<(), StorageError> {
+    #[cfg(feature = "web")]
+    if let Some(writes) = writes
+        .iter()
+        .map(|(storage, fields)| Some((storage.as_indexed_db()?, fields.clone())))
+        .collect::<Option<Vec<_>>>()
+    {
+        return IndexedDbStorage::write_stores(writes).await;
     }
+    for (storage, fields) in writes {
+        storage.write_fields(fields).await?;
+    }
+    Ok(())
+}
+
+/// Returns a handle on `storage` that encrypts fields under the key of `source`.
+///
+/// # Errors
+/// If the backend doesn't support encryption.
+pub fn encrypted(
+    storage: &dyn WalletStorage,
+    source: session::KeySource,
+) -> Result<Rc<dyn WalletStorage>, JsError> {
+    storage
+        .to_encrypted(source)
+        .ok_or_else(|| JsError::new("This wallet storage doesn't support encryption"))
 }
@@ -0,0 +1,416 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use async_trait::async_trait;
+use linera_base::time::{Duration, Instant};
+use rexie::{ObjectStore, Rexie, Transaction, TransactionMode};
+use serde::Serialize as _;
+use wasm_bindgen::{JsCast as _, JsValue};
+
+use super::{StorageError, WalletStorage};
+use crate::session::KeySource;
+
+/// How long closing a connection waits for the operations using it to complete.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often closing a connection checks whether it is still in use.
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+thread_local! {
+    /// The open connection to each database, by name, with the version it was opened
+    /// at. Every handle on the stores of a database shares its connection.
+    static CONNECTIONS: RefCell<HashMap<String, (u32, Rc<Rexie>)>> = RefCell::default();
+}
+
+/// An IndexedDB object store holding one value per field.
+///
+/// Values are stored as plain JS values. In encrypted mode (see
+/// [`IndexedDbStorage::encrypted`]) every field except [`crypto::KDF_FIELD`] is
+/// instead sealed with a key, usually the session's, before being written, and
+/// authenticated when read back, so the store is unusable while the session is locked.
+///
+/// The database is opened on first use, and the connection is shared with every other
+/// handle on it until the database is deleted or upgraded to a newer version.
+#[derive(Clone)]
+pub struct IndexedDbStorage {
+    db_name: String,
+    /// Every object store of the database, which must all be declared when opening it.
+    stores: Rc<[String]>,
+    store_name: String,
+    version: u32,
+    /// Where the key comes from, in encrypted mode.
+    encryption: Option<KeySource>,
+}
+
+impl IndexedDbStorage {
+    /// Returns a handle on `store_name`, the only object store of `db_name`.
+    pub fn new(db_name: impl Into<String>, store_name: impl Into<String>, version: u32) -> Self {
+        let store_name = store_name.into();
+        Self::in_database(db_name, &[store_name.as_str()], store_name.clone(), version)
+    }
+
+    /// Returns a handle on `store_name`, one of the object stores `stores` of
+    /// `db_name`.
+    pub fn in_database(
+        db_name: impl Into<String>,
+        stores: &[&str],
+        store_name: impl Into<String>,
+        version: u32,
+    ) -> Self {
+        Self {
+            db_name: db_name.into(),
+            stores: stores.iter().map(|store| store.to_string()).collect(),
+            store_name: store_name.into(),
+            version,
+            encryption: None,
+        }
+    }
+
+    /// Returns a handle on the same store that encrypts fields under the key of `source`.
+    #[must_use]
+    pub fn encrypted(self, source: KeySource) -> Self {
+        Self {
+            encryption: Some(source),
+            ..self
+        }
+    }
+
+    /// Converts a value about to be written under `field`, sealing it if the store is
+    /// encrypted.
+    fn encode(&self, field: &str, value: &serde_json::Value) -> Result<JsValue, StorageError> {
+        let Some(key) = super::field_key(self.encryption.as_ref(), field)? else {
+            return value
+                .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                .map_err(|e| StorageError::new(format!("Failed to serialize '{field}': {e}")));
+        };
+        let sealed = super::seal_value(&key, field, value)?;
+        Ok(js_sys::Uint8Array::from(sealed.as_slice()).into())
+    }
+
+    /// Converts a value read from `field`, opening it if the store is encrypted.
+    fn decode(&self, field: &str, value: JsValue) -> Result<serde_json::Value, StorageError> {
+        let Some(key) = super::field_key(self.encryption.as_ref(), field)? else {
+            // Older wallets stored maps as JS `Map`s rather than plain objects.
+            let value = match value.dyn_into::<js_sys::Map>() {
+                Ok(map) => js_sys::Object::from_entries(&map)
+                    .map_err(|_| StorageError::new(format!("Malformed map in '{field}'")))?
+                    .into(),
+                Err(value) => value,
+            };
+            return serde_wasm_bindgen::from_value(value)
+                .map_err(|e| StorageError::new(format!("Failed to deserialize '{field}': {e}")));
+        };
+        let sealed = value
+            .dyn_into::<js_sys::Uint8Array>()
+            .map_err(|_| StorageError::new(format!("Field '{field}' is not encrypted")))?
+            .to_vec();
+        super::open_value(&key, field, &sealed)
+    }
+
+    /// Deletes the whole database holding this store.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub async fn delete_database(&self) -> Result<(), StorageError> {
+        close(&self.db_name).await?;
+        Rexie::delete(&self.db_name)
+            .await
+            .map_err(|e| StorageError::new(format!("Failed to delete IndexedDB: {:?}", e)))
+    }
+
+    /// Moves every field of this store into `target` as they are, without decrypting
+    /// them, then deletes the database holding this store. Does nothing if that
+    /// database doesn't exist.
+    ///
+    /// # Errors
+    /// If storage is inaccessible.
+    pub async fn move_into(&self, target: &IndexedDbStorage) -> Result<(), StorageError> {
+        if !database_exists(&self.db_name).await? {
+            return Ok(());
+        }
+        let entries = self.read_raw_entries().await?;
+        if !entries.is_empty() {
+            let db = target.get_db().await?;
+            let tx = Self::transaction(&db, &[&target.store_name], TransactionMode::ReadWrite)?;
+            Self::put_all(&tx, &target.store_name, entries).await?;
+            Self::commit(tx).await?;
+        }
+        self.delete_database().await
+    }
+
+    /// Writes fields to several stores of the same database in a single transaction,
+    /// so that either all of them are written or none are.
+    ///
+    /// # Errors
+    /// If the stores aren't all in the same database, or if storage is inaccessible.
+    pub async fn write_stores(
+        writes: Vec<(&IndexedDbStorage, Vec<(String, serde_json::Value)>)>,
+    ) -> Result<(), StorageError> {
+        let Some((first, _)) = writes.first() else {
+            return Ok(());
+        };
+        if writes
+            .iter()
+            .any(|(storage, _)| storage.db_name != first.db_name)
+        {
+            return Err(StorageError::new(
+                "Stores of different databases can't be written in one transaction",
+            ));
+        }
+        let db = first.get_db().await?;
+        let writes = writes
+            .into_iter()
+            .map(|(storage, fields)| {
+                let entries = fields
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.clone(), storage.encode(&key, &value)?)))
+                    .collect::<Result<Vec<_>, StorageError>>()?;
+                Ok((storage.store_name.as_str(), entries))
+            })
+            .collect::<Result<Vec<_>, StorageError>>()?;
+
+        let store_names = writes.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let tx = Self::transaction(&db, &store_names, TransactionMode::ReadWrite)?;
+        for (store_name, entries) in writes {
+            Self::put_all(&tx, store_name, entries).await?;
+        }
+        Self::commit(tx).await
+    }
+
+    /// Returns the connection to the database, opening it if needed.
+    ///
+    /// A connection opened at an older version is closed first, as it would block the
+    /// upgrade.
+    async fn get_db(&self) -> Result<Rc<Rexie>, StorageError> {
+        let cached = CONNECTIONS.with_borrow(|connections| connections.get(&self.db_name).cloned());
+        if let Some((version, db)) = cached {
+            if version >= self.version {
+                return Ok(db);
+            }
+            // Our own reference would keep `close` waiting.
+            drop(db);
+            close(&self.db_name).await?;
+        }
+        let mut builder = Rexie::builder(&self.db_name).version(self.version);
+        for store in self.stores.iter() {
+            builder = builder.add_object_store(ObjectStore::new(store));
+        }
+        let db = Rc::new(
+            builder
+                .build()
+                .await
+                .map_err(|e| StorageError::new(format!("Failed to open IndexedDB: {:?}", e)))?,
+        );
+        // Another call may have opened the database in the meantime: keep one
+        // connection, and close ours so that it doesn't block upgrades and deletion
+        // until it is garbage-collected.
+        let (_, shared) = CONNECTIONS.with_borrow_mut(|connections| {
+            connections
+                .entry(self.db_name.clone())
+                .or_insert((self.version, db.clone()))
+                .clone()
+        });
+        if !Rc::ptr_eq(&shared, &db) {
+            if let Ok(db) = Rc::try_unwrap(db) {
+                db.close();
+            }
+        }
+        Ok(shared)
+    }
+
+    fn transaction(
+        db: &Rexie,
+        store_names: &[&str],
+        mode: TransactionMode,
+    ) -> Result<Transaction, StorageError> {
+        db.transaction(store_names, mode)
+            .map_err(|e| StorageError::new(format!("Failed to start transaction: {:?}", e)))
+    }
+
+    async fn commit(tx: Transaction) -> Result<(), StorageError> {
+        tx.done()
+            .await
+            .map_err(|e| StorageError::new(format!("Transaction failed: {:?}", e)))
+    }
+
+    async fn put_all(
+        tx: &Transaction,
+        store_name: &str,
+        entries: Vec<(String, JsValue)>,
+    ) -> Result<(), StorageError> {
+        let store = tx
+            .store(store_name)
+            .map_err(|e| StorageError::new(format!("Failed to open store: {:?}", e)))?;
+        let iter = entries
+            .into_iter()
+            .map(|(key, value)| (value, Some(JsValue::from_str(&key))));
+        store
+            .put_all(iter)
+            .await
+            .map_err(|e| StorageError::new(format!("Failed to write fields: {:?}", e)))
+    }
+
+    /// Reads every field of the store, without decoding them.
+    async fn read_raw_entries(&self) -> Result<Vec<(String, JsValue)>, StorageError> {
+        let db = self.get_db().await?;
+        let tx = Self::transaction(&db, &[&self.store_name], TransactionMode::ReadOnly)?;
+        let store = tx
+            .store(&self.store_name)
+            .map_err(|e| StorageError::new(format!("Failed to open store: {:?}", e)))?;
+
+        let keys = store
+            .get_all_keys(None, None)
+            .await
+            .map_err(|e| StorageError::new(format!("Failed to list keys: {:?}", e)))?;
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            let Some(field) = key.as_string() else {
+                continue;
+            };
+            if let Some(value) = store
+                .get(key)
+                .await
+                .map_err(|e| StorageError::new(format!("Failed to read '{}': {:?}", field, e)))?
+            {
+                entries.push((field, value));
+            }
+        }
+
+        Self::commit(tx).await?;
+        Ok(entries)
+    }
+}
+
+#[async_trait(?Send)]
+impl WalletStorage for IndexedDbStorage {
+    async fn read_field(&self, key: &str) -> Result<Option<serde_json::Value>, StorageError> {
+        Ok(self.read_fields(&[key]).await?.pop().flatten())
+    }
+
+    async fn read_fields(
+        &self,
+        keys: &[&str],
+    ) -> Result<Vec<Option<serde_json::Value>>, StorageError> {
+        let db = self.get_db().await?;
+        let tx = Self::transaction(&db, &[&self.store_name], TransactionMode::ReadOnly)?;
+        let store = tx
+            .store(&self.store_name)
+            .map_err(|e| StorageError::new(format!("Failed to open store: {:?}", e)))?;
+
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(
+                store
+                    .get(JsValue::from_str(key))
+                    .await
+                    .map_err(|e| StorageError::new(format!("Failed to read '{}': {:?}", key, e)))?,
+            );
+        }
+
+        // Wait for transaction to complete
+        Self::commit(tx).await?;
+
+        keys.iter()
+            .zip(values)
+            .map(|(key, value)| value.map(|value| self.decode(key, value)).transpose())
+            .collect()
+    }
+
+    async fn write_field(&self, key: &str, value: serde_json::Value) -> Result<(), StorageError> {
+        self.write_fields(vec![(key.to_string(), value)]).await
+    }
+
+    async fn write_fields(
+        &self,
+        fields: Vec<(String, serde_json::Value)>,
+    ) -> Result<(), StorageError> {
+        Self::write_stores(vec![(self, fields)]).await
+    }
+
+    async fn read_all_fields(&self) -> Result<Vec<(String, serde_json::Value)>, StorageError> {
+        self.read_raw_entries()
+            .await?
+            .into_iter()
+            .map(|(field, value)| Ok((field.clone(), self.decode(&field, value)?)))
+            .collect()
+    }
+
+    async fn remove_field(&self, key: &str) -> Result<(), StorageError> {
+        let db = self.get_db().await?;
+        let tx = Self::transaction(&db, &[&self.store_name], TransactionMode::ReadWrite)?;
+        tx.store(&self.store_name)
+            .map_err(|e| StorageError::new(format!("Failed to open store: {:?}", e)))?
+            .delete(JsValue::from_str(key))
+            .await
+            .map_err(|e| StorageError::new(format!("Failed to remove '{}': {:?}", key, e)))?;
+        Self::commit(tx).await
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    fn to_encrypted(&self, source: KeySource) -> Option<Rc<dyn WalletStorage>> {
+        Some(Rc::new(self.clone().encrypted(source)))
+    }
+
+    fn as_indexed_db(&self) -> Option<&IndexedDbStorage> {
+        Some(self)
+    }
+}
+
+/// Closes the connection to the database `db_name`, if there is one, so that it can be
+/// deleted or upgraded, once the operations using it complete.
+///
+/// # Errors
+/// If the connection is still in use after [`CLOSE_TIMEOUT`]: deleting or upgrading
+/// the database would block until it is released.
+async fn close(db_name: &str) -> Result<(), StorageError> {
+    let Some((_, mut db)) = CONNECTIONS.with_borrow_mut(|connections| connections.remove(db_name))
+    else {
+        return Ok(());
+    };
+    let deadline = Instant::now() + CLOSE_TIMEOUT;
+    loop {
+        match Rc::try_unwrap(db) {
+            Ok(db) => {
+                db.close();
+                return Ok(());
+            }
+            Err(shared) if Instant::now() < deadline => {
+                db = shared;
+                crate::runtime::sleep(CLOSE_POLL_INTERVAL)
+                    .await
+                    .map_err(|e| StorageError::new(e.message()))?;
+            }
+            Err(_) => {
+                return Err(StorageError::new(format!(
+                    "The IndexedDB database '{db_name}' is still in use"
+                )))
+            }
+        }
+    }
+}
+
+/// Whether the database `db_name` exists, without creating it. Assumes it does if the
+/// browser can't list databases.
+async fn database_exists(db_name: &str) -> Result<bool, StorageError> {
+    let factory = js_sys::Reflect::get(&js_sys::global(), &"indexedDB".into())
+        .map_err(|_| StorageError::new("IndexedDB is unavailable"))?;
+    let Ok(databases) = js_sys::Reflect::get(&factory, &"databases".into())
+        .and_then(|databases| databases.dyn_into::<js_sys::Function>().map_err(Into::into))
+    else {
+        return Ok(true);
+    };
+    let promise = databases
+        .call0(&factory)
+        .and_then(|promise| promise.dyn_into::<js_sys::Promise>().map_err(Into::into))
+        .map_err(|e| StorageError::new(format!("Failed to list IndexedDB databases: {e:?}")))?;
+    let databases = wasm_bindgen_futures::JsFuture::from(promise)
+        .await
+        .map_err(|e| StorageError::new(format!("Failed to list IndexedDB databases: {e:?}")))?;
+    Ok(js_sys::Array::from(&databases).iter().any(|info| {
+        js_sys::Reflect::get(&info, &"name".into())
+            .ok()
+            .and_then(|name| name.as_string())
+            .is_some_and(|name| name == db_name)
+    }))
+}
@@ -0,0 +1,332 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! [`WalletStorage`] backends other than IndexedDB.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use async_trait::async_trait;
+
+use crate::utils::{StorageError, WalletStorage};
+
+/// A store kept in memory, e.g. for tests. Clones share the same fields.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    fields: Rc<RefCell<BTreeMap<String, serde_json::Value>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait(?Send)]
+impl WalletStorage for MemoryStorage {
+    async fn read_field(&self, key: &str) -> Result<Option<serde_json::Value>, StorageError> {
+        Ok(self.fields.borrow().get(key).cloned())
+    }
+
+    async fn write_field(&self, key: &str, value: serde_json::Value) -> Result<(), StorageError> {
+        self.fields.borrow_mut().insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn write_fields(
+        &self,
+        fields: Vec<(String, serde_json::Value)>,
+    ) -> Result<(), StorageError> {
+        self.fields.borrow_mut().extend(fields);
+        Ok(())
+    }
+
+    async fn read_all_fields(&self) -> Result<Vec<(String, serde_json::Value)>, StorageError> {
+        Ok(self
+            .fields
+            .borrow()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    async fn remove_field(&self, key: &str) -> Result<(), StorageError> {
+        self.fields.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "web"))]
+pub use file::FileStorage;
+
+#[cfg(not(feature = "web"))]
+mod file {
+    use std::{
+        collections::BTreeMap,
+        fs, io,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    use async_trait::async_trait;
+
+    use crate::{
+        session::KeySource,
+        utils::{self, StorageError, WalletStorage},
+    };
+
+    /// A store kept in a JSON file on the local filesystem, for native tooling.
+    ///
+    /// Every write replaces the whole file atomically, by writing a temporary file
+    /// next to it and renaming it over the original.
+    ///
+    /// In encrypted mode (see [`FileStorage::encrypted`]) every field except
+    /// `crypto::KDF_FIELD` is sealed like in IndexedDB, and kept in the file as a hex
+    /// string.
+    #[derive(Clone, Debug)]
+    pub struct FileStorage {
+        path: PathBuf,
+        /// Where the key comes from, in encrypted mode.
+        encryption: Option<KeySource>,
+    }
+
+    impl FileStorage {
+        /// Returns a handle on the store at `path`, which is created on the first write.
+        pub fn new(path: impl Into<PathBuf>) -> Self {
+            Self {
+                path: path.into(),
+                encryption: None,
+            }
+        }
+
+        /// Returns a handle on the same store that encrypts fields under the key of
+        /// `source`.
+        #[must_use]
+        pub fn encrypted(self, source: KeySource) -> Self {
+            Self {
+                encryption: Some(source),
+                ..self
+            }
+        }
+
+        /// The path of the file holding the store.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// The directory holding the stores of native builds: `$LINERA_WEB_DATA_DIR`,
+        /// or `.linera-web` in the working directory.
+        pub fn data_dir() -> PathBuf {
+            std::env::var_os("LINERA_WEB_DATA_DIR")
+                .map_or_else(|| PathBuf::from(".linera-web"), PathBuf::from)
+        }
+
+        /// Points [`FileStorage::data_dir`] at a directory of the test process, so
+        /// that tests don't write to the working directory.
+        #[cfg(test)]
+        pub(crate) fn use_test_data_dir() {
+            static ONCE: std::sync::Once = std::sync::Once::new();
+            ONCE.call_once(|| {
+                std::env::set_var(
+                    "LINERA_WEB_DATA_DIR",
+                    std::env::temp_dir().join(format!("linera-web-data-{}", std::process::id())),
+                );
+            });
+        }
+
+        /// Deletes the file holding the store, and the directory holding it if it is
+        /// left empty.
+        ///
+        /// Unlike an IndexedDB database, this leaves the other stores of the database
+        /// alone: each is a file of its own.
+        ///
+        /// # Errors
+        /// If the file exists but can't be removed.
+        pub async fn delete_database(&self) -> Result<(), StorageError> {
+            match fs::remove_file(&self.path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    return Err(self.error("delete", &error));
+                }
+                _ => {}
+            }
+            if let Some(parent) = self.path.parent() {
+                // This fails if other stores remain, which is fine.
+                let _ = fs::remove_dir(parent);
+            }
+            Ok(())
+        }
+
+        /// Moves every field of this store into `target`, then deletes this store.
+        ///
+        /// # Errors
+        /// If either file can't be read or written.
+        pub async fn move_into(&self, target: &FileStorage) -> Result<(), StorageError> {
+            let fields = self.read()?;
+            if !fields.is_empty() {
+                let mut target_fields = target.read()?;
+                target_fields.extend(fields);
+                target.write(&target_fields)?;
+            }
+            self.delete_database().await
+        }
+
+        /// Converts a value about to be written under `field`, sealing it if the store
+        /// is encrypted.
+        fn encode(
+            &self,
+            field: &str,
+            value: serde_json::Value,
+        ) -> Result<serde_json::Value, StorageError> {
+            match utils::field_key(self.encryption.as_ref(), field)? {
+                Some(key) => Ok(hex::encode(utils::seal_value(&key, field, &value)?).into()),
+                None => Ok(value),
+            }
+        }
+
+        /// Converts a value read from `field`, opening it if the store is encrypted.
+        fn decode(
+            &self,
+            field: &str,
+            value: serde_json::Value,
+        ) -> Result<serde_json::Value, StorageError> {
+            let Some(key) = utils::field_key(self.encryption.as_ref(), field)? else {
+                return Ok(value);
+            };
+            let sealed = value
+                .as_str()
+                .and_then(|sealed| hex::decode(sealed).ok())
+                .ok_or_else(|| StorageError::new(format!("Field '{field}' is not encrypted")))?;
+            utils::open_value(&key, field, &sealed)
+        }
+
+        fn read(&self) -> Result<BTreeMap<String, serde_json::Value>, StorageError> {
+            let contents = match fs::read(&self.path) {
+                Ok(contents) => contents,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+                Err(error) => return Err(self.error("read", &error)),
+            };
+            serde_json::from_slice(&contents).map_err(|e| self.error("parse", &e))
+        }
+
+        fn write(&self, fields: &BTreeMap<String, serde_json::Value>) -> Result<(), StorageError> {
+            let contents =
+                serde_json::to_vec_pretty(fields).map_err(|e| self.error("serialize", &e))?;
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent).map_err(|e| self.error("write", &e))?;
+            }
+            let temporary = self.path.with_extension("tmp");
+            fs::write(&temporary, contents).map_err(|e| self.error("write", &e))?;
+            fs::rename(&temporary, &self.path).map_err(|e| self.error("write", &e))
+        }
+
+        fn error(&self, action: &str, error: &dyn std::fmt::Display) -> StorageError {
+            StorageError::new(format!(
+                "Failed to {action} {}: {error}",
+                self.path.display()
+            ))
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl WalletStorage for FileStorage {
+        async fn read_field(&self, key: &str) -> Result<Option<serde_json::Value>, StorageError> {
+            self.read()?
+                .remove(key)
+                .map(|value| self.decode(key, value))
+                .transpose()
+        }
+
+        async fn read_fields(
+            &self,
+            keys: &[&str],
+        ) -> Result<Vec<Option<serde_json::Value>>, StorageError> {
+            let mut fields = self.read()?;
+            keys.iter()
+                .map(|key| {
+                    fields
+                        .remove(*key)
+                        .map(|value| self.decode(key, value))
+                        .transpose()
+                })
+                .collect()
+        }
+
+        async fn write_field(
+            &self,
+            key: &str,
+            value: serde_json::Value,
+        ) -> Result<(), StorageError> {
+            self.write_fields(vec![(key.to_string(), value)]).await
+        }
+
+        async fn write_fields(
+            &self,
+            new_fields: Vec<(String, serde_json::Value)>,
+        ) -> Result<(), StorageError> {
+            let mut fields = self.read()?;
+            for (key, value) in new_fields {
+                let value = self.encode(&key, value)?;
+                fields.insert(key, value);
+            }
+            self.write(&fields)
+        }
+
+        async fn read_all_fields(&self) -> Result<Vec<(String, serde_json::Value)>, StorageError> {
+            self.read()?
+                .into_iter()
+                .map(|(key, value)| Ok((key.clone(), self.decode(&key, value)?)))
+                .collect()
+        }
+
+        async fn remove_field(&self, key: &str) -> Result<(), StorageError> {
+            let mut fields = self.read()?;
+            if fields.remove(key).is_some() {
+                self.write(&fields)?;
+            }
+            Ok(())
+        }
+
+        fn is_encrypted(&self) -> bool {
+            self.encryption.is_some()
+        }
+
+        fn to_encrypted(&self, source: KeySource) -> Option<Rc<dyn WalletStorage>> {
+            Some(Rc::new(self.clone().encrypted(source)))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use futures::executor::block_on;
+
+        use super::*;
+        use crate::crypto::{KdfParams, KDF_FIELD};
+
+        #[test]
+        fn encrypted_fields_round_trip() {
+            let path = std::env::temp_dir()
+                .join(format!("linera-web-test-{}", std::process::id()))
+                .join("wallet.json");
+            let (params, key) = KdfParams::generate("password").unwrap();
+            let storage = FileStorage::new(&path).encrypted(KeySource::Key(Rc::new(key)));
+            let value = serde_json::json!({ "chains": ["a", "b"] });
+            block_on(storage.write_fields(vec![
+                ("wallet".to_string(), value.clone()),
+                (
+                    KDF_FIELD.to_string(),
+                    utils::kdf_params_value(&params).unwrap(),
+                ),
+            ]))
+            .unwrap();
+
+            // The file holds the field sealed, and the parameters as they are.
+            let plain = FileStorage::new(&path);
+            assert!(block_on(plain.read_field("wallet"))
+                .unwrap()
+                .is_some_and(|sealed| sealed.is_string() && sealed != value));
+            assert!(block_on(plain.read_kdf_params()).unwrap().is_some());
+
+            assert_eq!(block_on(storage.read_field("wallet")).unwrap(), Some(value));
+            block_on(storage.delete_database()).unwrap();
+        }
+    }
+}
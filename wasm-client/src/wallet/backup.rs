@@ -0,0 +1,195 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The portable backup format of the wallet.
+//!
+//! A backup is a JSON document describing itself: its format and version, the network
+//! it belongs to and the hash of that network's genesis config. Its payload is the
+//! wallet as accepted by `Wallet.import` (chains, default chain, genesis config and
+//! chain metadata),
+//! either in the clear or sealed under a backup password, and it carries the SHA-256
+//! checksum of that payload as stored, which is verified on import. For a sealed
+//! payload that is the checksum of the ciphertext, so that nothing about the
+//! plaintext is revealed.
+
+use std::collections::BTreeMap;
+
+use linera_base::identifiers::ChainId;
+use linera_client::config::GenesisConfig;
+use linera_core::wallet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use wasm_bindgen::prelude::*;
+
+use super::{metadata::Metadata, Wallet};
+use crate::{crypto::KdfParams, JsError, JsResult};
+
+/// The value of the `format` field identifying a backup.
+pub const FORMAT: &str = "linera-wallet-backup";
+/// The version of the backup format written by this code.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The associated data binding the sealed payload to the backup format.
+const PAYLOAD_FIELD: &str = "backup:wallet";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    format: String,
+    version: u32,
+    network: String,
+    genesis_hash: String,
+    /// Hex-encoded SHA-256 of the payload as stored: the wallet JSON, or the sealed
+    /// bytes if it is encrypted.
+    checksum: String,
+    payload: Payload,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Payload {
+    Plain { wallet: String },
+    Encrypted { kdf: KdfParams, ciphertext: String },
+}
+
+#[derive(Serialize)]
+struct WalletContents<'a> {
+    chains: BTreeMap<ChainId, wallet::Chain>,
+    default: Option<ChainId>,
+    genesis: &'a GenesisConfig,
+    metadata: &'a Metadata,
+}
+
+/// Whether `json` looks like a backup rather than a bare wallet.
+pub(super) fn is_backup(json: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Header {
+        format: Option<String>,
+    }
+    serde_json::from_str::<Header>(json)
+        .is_ok_and(|header| header.format.as_deref() == Some(FORMAT))
+}
+
+/// Writes `wallet` as a backup, sealing it under `password` if one is given.
+///
+/// # Errors
+/// If the wallet can't be serialized or the key can't be derived.
+pub(super) fn export(wallet: &Wallet, password: Option<&str>) -> JsResult<String> {
+    let chains = wallet
+        .chains
+        .chain_ids()
+        .into_iter()
+        .filter_map(|chain_id| Some((chain_id, wallet.chains.get(chain_id)?)))
+        .collect();
+    let metadata = wallet.metadata.borrow();
+    let contents = serde_json::to_string(&WalletContents {
+        chains,
+        default: wallet.default.get(),
+        genesis: &wallet.genesis_config,
+        metadata: &metadata,
+    })
+    .map_err(|e| JsError::new(&format!("Failed to serialize wallet: {e}")))?;
+
+    let (checksum, payload) = match password {
+        None => (
+            checksum(contents.as_bytes()),
+            Payload::Plain { wallet: contents },
+        ),
+        Some(password) => {
+            let (kdf, key) = KdfParams::generate(password)?;
+            let sealed = key.seal(PAYLOAD_FIELD, contents.as_bytes())?;
+            (
+                checksum(&sealed),
+                Payload::Encrypted {
+                    kdf,
+                    ciphertext: hex::encode(sealed),
+                },
+            )
+        }
+    };
+
+    serde_json::to_string_pretty(&Backup {
+        format: FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        network: wallet.genesis_config.network_name.clone(),
+        genesis_hash: wallet.genesis_config.hash().to_string(),
+        checksum,
+        payload,
+    })
+    .map_err(|e| JsError::new(&format!("Failed to serialize backup: {e}")))
+}
+
+/// Opens a backup, decrypting it with `password` if it is encrypted, verifies its
+/// checksum and genesis hash, and returns the wallet JSON it contains.
+///
+/// # Errors
+/// If the backup is malformed, of another format or an unsupported version, encrypted
+/// and `password` is missing or incorrect, or fails verification.
+pub(super) fn open(json: &str, password: Option<&str>) -> JsResult<String> {
+    let backup: Backup = serde_json::from_str(json)
+        .map_err(|e| JsError::new(&format!("Malformed wallet backup: {e}")))?;
+    if backup.format != FORMAT {
+        return Err(JsError::new(&format!(
+            "Not a wallet backup: unknown format {:?}",
+            backup.format
+        )));
+    }
+    if backup.version > FORMAT_VERSION {
+        return Err(JsError::new(&format!(
+            "The backup uses format version {}, but only versions up to {FORMAT_VERSION} \
+             are supported: please update",
+            backup.version
+        )));
+    }
+    if backup.version != FORMAT_VERSION {
+        return Err(JsError::new(&format!(
+            "Unsupported backup format version {}",
+            backup.version
+        )));
+    }
+
+    let expected = backup.checksum.to_lowercase();
+    let verify = |bytes: &[u8]| {
+        if checksum(bytes) == expected {
+            Ok(())
+        } else {
+            Err(JsError::new(
+                "The backup checksum doesn't match: the file is corrupted",
+            ))
+        }
+    };
+    let contents = match backup.payload {
+        Payload::Plain { wallet } => {
+            verify(wallet.as_bytes())?;
+            wallet
+        }
+        Payload::Encrypted { kdf, ciphertext } => {
+            let password = password
+                .ok_or_else(|| JsError::new("The backup is encrypted: a password is required"))?;
+            let sealed = hex::decode(ciphertext)
+                .map_err(|e| JsError::new(&format!("Malformed backup ciphertext: {e}")))?;
+            verify(&sealed)?;
+            let plaintext = kdf.unlock(password)?.open(PAYLOAD_FIELD, &sealed)?;
+            String::from_utf8(plaintext.to_vec())
+                .map_err(|e| JsError::new(&format!("Malformed backup payload: {e}")))?
+        }
+    };
+
+    #[derive(Deserialize)]
+    struct Genesis {
+        genesis: GenesisConfig,
+    }
+    let Genesis { genesis } = serde_json::from_str(&contents)
+        .map_err(|e| JsError::new(&format!("Malformed backup payload: {e}")))?;
+    if genesis.hash().to_string() != backup.genesis_hash {
+        return Err(JsError::new(
+            "The backup's genesis config doesn't match its genesis hash",
+        ));
+    }
+
+    Ok(contents)
+}
+
+fn checksum(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
@@ -0,0 +1,290 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validation and merging of imported wallets.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    rc::Rc,
+};
+
+use linera_base::{
+    data_types::{BlockHeight, ChainDescription},
+    identifiers::{AccountOwner, ChainId},
+};
+use linera_client::config::GenesisConfig;
+use linera_core::wallet;
+use wasm_bindgen::prelude::*;
+
+use super::{
+    metadata::{self, Metadata},
+    Wallet,
+};
+use crate::{JsError, JsResult, WalletChains};
+
+/// How an imported wallet is combined with the stored one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, tsify::Tsify)]
+#[serde(rename_all = "lowercase")]
+#[tsify(from_wasm_abi)]
+pub enum ImportMode {
+    /// Overwrite the stored wallet.
+    Replace,
+    /// Add the imported chains to the stored wallet, keeping existing entries.
+    Merge,
+}
+
+/// A chain present in both wallets with different entries. The stored entry is kept.
+#[derive(Debug, serde::Serialize, tsify::Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConflict {
+    pub chain_id: ChainId,
+    pub reason: String,
+}
+
+/// The outcome of an import.
+#[derive(Debug, Default, serde::Serialize, tsify::Tsify)]
+#[serde(rename_all = "camelCase")]
+#[tsify(into_wasm_abi)]
+pub struct ImportReport {
+    /// Chains added to the wallet.
+    pub imported: Vec<ChainId>,
+    /// Chains that were already in the wallet with the same entry.
+    pub unchanged: Vec<ChainId>,
+    /// Chains that were already in the wallet with a different entry.
+    pub conflicts: Vec<ImportConflict>,
+    /// The default chain of the resulting wallet.
+    pub default: Option<ChainId>,
+}
+
+/// A wallet parsed from JSON and checked for internal consistency.
+pub(super) struct ImportedWallet {
+    chains: BTreeMap<ChainId, wallet::Chain>,
+    default: Option<ChainId>,
+    genesis_config: Option<GenesisConfig>,
+    metadata: Metadata,
+}
+
+#[derive(serde::Deserialize)]
+struct WalletJson {
+    chains: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    default: Option<String>,
+    #[serde(default)]
+    genesis: Option<serde_json::Value>,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+impl ImportedWallet {
+    /// Parses and validates `{ chains, default, genesis, metadata }`, where `genesis`
+    /// may be either an object or a JSON string, and `metadata` is optional.
+    pub(super) fn parse(json: &str) -> JsResult<Self> {
+        let WalletJson {
+            chains: chain_values,
+            default,
+            genesis,
+            metadata,
+        } = serde_json::from_str(json)
+            .map_err(|e| JsError::new(&format!("Malformed wallet JSON: {e}")))?;
+
+        let mut chains = BTreeMap::new();
+        for (id, value) in chain_values {
+            let chain_id: ChainId = id
+                .parse()
+                .map_err(|e| JsError::new(&format!("Invalid chain ID {id:?}: {e}")))?;
+            let chain: wallet::Chain = serde_json::from_value(value)
+                .map_err(|e| JsError::new(&format!("Invalid entry for chain {chain_id}: {e}")))?;
+            chains.insert(chain_id, chain);
+        }
+
+        let default = default
+            .map(|id| {
+                id.parse::<ChainId>()
+                    .map_err(|e| JsError::new(&format!("Invalid default chain ID {id:?}: {e}")))
+            })
+            .transpose()?;
+        if let Some(default) = default {
+            if !chains.contains_key(&default) {
+                return Err(JsError::new(&format!(
+                    "The default chain {default} is not in the wallet"
+                )));
+            }
+        }
+
+        let genesis_config = genesis
+            .map(|genesis| match genesis {
+                serde_json::Value::String(json) => serde_json::from_str(&json),
+                value => serde_json::from_value(value),
+            })
+            .transpose()
+            .map_err(|e| JsError::new(&format!("Invalid genesis config: {e}")))?;
+
+        let metadata = metadata::parse(metadata)?;
+        for (chain_id, entry) in &metadata {
+            if !chains.contains_key(chain_id) {
+                return Err(JsError::new(&format!(
+                    "There is metadata for chain {chain_id}, which is not in the wallet"
+                )));
+            }
+            if let Some(label) = &entry.label {
+                metadata::check_label(&metadata, *chain_id, label)?;
+            }
+        }
+
+        Ok(Self {
+            chains,
+            default,
+            genesis_config,
+            metadata,
+        })
+    }
+
+    /// Checks the entry of `chain_id` on its own and against the network of
+    /// `genesis_config`: a genesis chain must match its description, and no chain can
+    /// predate the network.
+    fn check_chain(
+        chain_id: ChainId,
+        chain: &wallet::Chain,
+        genesis_config: &GenesisConfig,
+    ) -> JsResult<()> {
+        if chain.owner == Some(AccountOwner::CHAIN) {
+            return Err(JsError::new(&format!(
+                "Chain {chain_id} has the reserved chain account as its owner"
+            )));
+        }
+        if chain.block_hash.is_some() == (chain.next_block_height == BlockHeight::ZERO) {
+            return Err(JsError::new(&format!(
+                "Chain {chain_id} has an inconsistent block hash and height"
+            )));
+        }
+        if let Some(description) = genesis_config
+            .chains
+            .iter()
+            .find(|description| description.id() == chain_id)
+        {
+            if chain.timestamp != description.timestamp() {
+                return Err(JsError::new(&format!(
+                    "Chain {chain_id} doesn't match its description in the genesis config"
+                )));
+            }
+        } else if let Some(genesis_timestamp) = genesis_config
+            .chains
+            .iter()
+            .map(ChainDescription::timestamp)
+            .min()
+        {
+            if chain.timestamp < genesis_timestamp {
+                return Err(JsError::new(&format!(
+                    "Chain {chain_id} was created before the network of the genesis config"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Combines the imported wallet with the stored one, if any, according to `mode`.
+    ///
+    /// The imported chains are checked against the genesis config of the result.
+    pub(super) fn apply(
+        self,
+        existing: Option<Wallet>,
+        mode: ImportMode,
+    ) -> JsResult<(Wallet, ImportReport)> {
+        let genesis_config = match (&existing, self.genesis_config) {
+            (Some(existing), Some(imported))
+                if existing.genesis_config.hash() != imported.hash() =>
+            {
+                return Err(JsError::new(&format!(
+                    "The imported wallet is for network {:?}, but this wallet is for network {:?}",
+                    imported.network_name, existing.genesis_config.network_name,
+                )));
+            }
+            (_, Some(imported)) => imported,
+            (Some(existing), None) => existing.genesis_config.clone(),
+            (None, None) => {
+                return Err(JsError::new(
+                    "The imported wallet has no genesis config, and there is no wallet to \
+                     take it from",
+                ))
+            }
+        };
+
+        for (chain_id, chain) in &self.chains {
+            Self::check_chain(*chain_id, chain, &genesis_config)?;
+        }
+
+        let mut report = ImportReport::default();
+        let memory = wallet::Memory::default();
+        let mut default = self.default;
+        let mut metadata = Metadata::new();
+
+        if let (Some(existing), ImportMode::Merge) = (existing, mode) {
+            for chain_id in existing.chains.chain_ids() {
+                if let Some(chain) = existing.chains.get(chain_id) {
+                    memory.insert(chain_id, chain);
+                }
+            }
+            default = existing.default.get().or(default);
+            metadata = existing.metadata.borrow().clone();
+        }
+
+        let mut imported_metadata = self.metadata;
+        for (chain_id, chain) in self.chains {
+            let Some(current) = memory.get(chain_id) else {
+                memory.insert(chain_id, chain);
+                report.imported.push(chain_id);
+                if let Some(mut entry) = imported_metadata.remove(&chain_id) {
+                    if let Some(label) = &entry.label {
+                        if metadata::check_label(&metadata, chain_id, label).is_err() {
+                            tracing::warn!(
+                                "dropping the label {label:?} of imported chain {chain_id}: \
+                                 it is already used"
+                            );
+                            entry.label = None;
+                        }
+                    }
+                    metadata.insert(chain_id, entry);
+                }
+                continue;
+            };
+            if let Some(reason) = Self::conflict(&current, &chain)? {
+                report.conflicts.push(ImportConflict { chain_id, reason });
+            } else {
+                report.unchanged.push(chain_id);
+            }
+        }
+
+        report.default = default;
+        Ok((
+            Wallet {
+                chains: WalletChains::new(memory),
+                default: Rc::new(Cell::new(default)),
+                default_hooks: Rc::default(),
+                genesis_config,
+                metadata: Rc::new(RefCell::new(metadata)),
+                wiped: Rc::default(),
+                lock_hook: Rc::default(),
+            },
+            report,
+        ))
+    }
+
+    /// Describes how two entries for the same chain differ, if they do.
+    fn conflict(current: &wallet::Chain, imported: &wallet::Chain) -> JsResult<Option<String>> {
+        if current.owner != imported.owner {
+            return Ok(Some("the owners differ".to_string()));
+        }
+        let to_json = |chain: &wallet::Chain| {
+            serde_json::to_value(chain)
+                .map_err(|e| JsError::new(&format!("Failed to serialize chain: {e}")))
+        };
+        if to_json(current)? != to_json(imported)? {
+            return Ok(Some(format!(
+                "the chain states differ (stored height {}, imported height {})",
+                current.next_block_height, imported.next_block_height
+            )));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wallet-side metadata about chains, which the protocol doesn't know about: a label
+//! to tell them apart, a tag, notes, whether to hide them, and how they were added.
+//!
+//! The metadata is stored in its own wallet field next to `chains`, keyed by chain ID.
+//! Labels are unique within a wallet, and can be used wherever a chain ID is accepted.
+
+use std::collections::BTreeMap;
+
+use linera_base::identifiers::ChainId;
+use serde::{Deserialize, Serialize};
+
+use crate::{JsError, JsResult};
+
+/// The wallet field holding the metadata of every chain.
+pub const FIELD: &str = "metadata";
+
+const MAX_LABEL_LEN: usize = 64;
+
+/// How a chain came to be in the wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, tsify::Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum CreatedVia {
+    /// Claimed from a faucet.
+    Faucet,
+    /// Assigned to a key with `Client.assignChain`.
+    Assigned,
+    /// Opened from another chain of the wallet.
+    Opened,
+}
+
+/// What the wallet knows about a chain besides its protocol state.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, tsify::Tsify)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainMetadata {
+    /// A name for the chain, unique within the wallet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// A color or tag to group chains with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Free-form notes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Whether the chain is left out of chain lists by default.
+    #[serde(default)]
+    pub hidden: bool,
+    /// How the chain was added. This is set by the wallet and can't be edited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_via: Option<CreatedVia>,
+}
+
+/// A chain of the wallet along with its metadata, as listed by `Wallet.listChains`.
+#[derive(Serialize, tsify::Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainEntry {
+    pub chain_id: ChainId,
+    pub is_default: bool,
+    #[serde(flatten)]
+    pub metadata: ChainMetadata,
+}
+
+/// The metadata of the chains of a wallet.
+pub type Metadata = BTreeMap<ChainId, ChainMetadata>;
+
+/// Parses the stored metadata field, which is missing in wallets written before
+/// metadata existed.
+///
+/// # Errors
+/// If the field is malformed.
+pub fn parse(value: Option<serde_json::Value>) -> JsResult<Metadata> {
+    value
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| JsError::new(&format!("Failed to deserialize chain metadata: {e}")))
+        .map(Option::unwrap_or_default)
+}
+
+/// Checks that `label` can be given to `chain_id`: it must be non-empty, not look
+/// like a chain ID, and not be used by another chain.
+///
+/// # Errors
+/// If the label is invalid or taken.
+pub fn check_label(metadata: &Metadata, chain_id: ChainId, label: &str) -> JsResult<()> {
+    if label.trim().is_empty() || label.trim() != label {
+        return Err(JsError::new(
+            "Chain labels must be non-empty, without leading or trailing spaces",
+        ));
+    }
+    if label.chars().count() > MAX_LABEL_LEN {
+        return Err(JsError::new(&format!(
+            "Chain labels must be at most {MAX_LABEL_LEN} characters long"
+        )));
+    }
+    if label.parse::<ChainId>().is_ok() {
+        return Err(JsError::new("A chain label can't be a chain ID"));
+    }
+    if let Some((other, _)) = metadata
+        .iter()
+        .find(|(other, entry)| **other != chain_id && entry.label.as_deref() == Some(label))
+    {
+        return Err(JsError::new(&format!(
+            "The label {label:?} is already used by chain {other}"
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves `chain`, either a chain ID or the label of a chain, to a chain ID.
+///
+/// # Errors
+/// If `chain` is neither a chain ID nor a known label.
+pub fn resolve(metadata: &Metadata, chain: &str) -> JsResult<ChainId> {
+    if let Ok(chain_id) = chain.parse() {
+        return Ok(chain_id);
+    }
+    metadata
+        .iter()
+        .find(|(_, entry)| entry.label.as_deref() == Some(chain))
+        .map(|(chain_id, _)| *chain_id)
+        .ok_or_else(|| JsError::new(&format!("No chain ID or chain label {chain:?}")))
+}
@@ -0,0 +1,853 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use linera_base::identifiers::{AccountOwner, ChainId};
+use linera_client::config::GenesisConfig;
+use linera_core::wallet;
+use linera_core::wallet::Chain;
+use wasm_bindgen::prelude::*;
+
+mod backend;
+mod backup;
+mod import;
+pub mod metadata;
+pub mod schema;
+
+#[cfg(not(feature = "web"))]
+pub use backend::FileStorage;
+pub use backend::MemoryStorage;
+pub use import::{ImportConflict, ImportMode, ImportReport};
+pub use metadata::{ChainEntry, ChainMetadata, CreatedVia};
+
+use self::import::ImportedWallet;
+use crate::{
+    crypto::{self, KdfParams},
+    profile::{self, Profile},
+    session::{self, KeySource},
+    utils::{self, SecretVault, WalletStorage},
+    JsError,
+};
+
+#[allow(unused_imports)]
+use super::JsResult;
+
+#[wasm_bindgen(js_name = "Wallet")]
+#[derive(Clone)]
+pub struct PersistentWallet {
+    pub(crate) wallet: Wallet,
+    storage: Rc<dyn WalletStorage>,
+    /// The profile the wallet belongs to.
+    pub(crate) profile: Profile,
+}
+
+impl PersistentWallet {
+    /// Wraps `wallet`, read from or to be written to `storage`.
+    ///
+    /// If `storage` is encrypted, the wallet is wiped from memory when the session
+    /// locks.
+    pub fn new(wallet: Wallet, storage: Rc<dyn WalletStorage>, profile: Profile) -> Self {
+        if storage.is_encrypted() {
+            wallet.wipe_on_lock();
+        }
+        Self {
+            wallet,
+            storage,
+            profile,
+        }
+    }
+
+    /// Reads the wallet from `storage`, bypassing the profile registry and the
+    /// session, or returns `None` if there is no wallet.
+    ///
+    /// # Errors
+    /// If storage is inaccessible, or if the stored wallet is malformed.
+    pub async fn open(
+        storage: impl WalletStorage + 'static,
+        profile: Profile,
+    ) -> Result<Option<Self>, JsError> {
+        schema::migrate(&storage).await?;
+        Ok(Self::load(&storage)
+            .await?
+            .map(|wallet| Self::new(wallet, Rc::new(storage), profile)))
+    }
+
+    /// Lists the chains of the wallet with their metadata, leaving out hidden chains
+    /// unless `include_hidden` is set.
+    pub fn chain_entries(&self, include_hidden: bool) -> Vec<ChainEntry> {
+        let metadata = self.wallet.metadata.borrow();
+        self.wallet
+            .chains
+            .chain_ids()
+            .into_iter()
+            .map(|chain_id| ChainEntry {
+                chain_id,
+                is_default: self.wallet.default.get() == Some(chain_id),
+                metadata: metadata.get(&chain_id).cloned().unwrap_or_default(),
+            })
+            .filter(|entry| include_hidden || !entry.metadata.hidden)
+            .collect()
+    }
+}
+
+#[wasm_bindgen(js_class = "Wallet")]
+impl PersistentWallet {
+    /// Attempts to read the wallet of the selected profile from persistent storage.
+    ///
+    /// If the wallet is encrypted, it is unlocked with `password`; without one, the
+    /// session must already be unlocked.
+    ///
+    /// # Errors
+    /// If storage is inaccessible, or if the wallet is encrypted and `password` is
+    /// incorrect or the session is locked.
+    #[wasm_bindgen(js_name = "get")]
+    pub async fn get(password: Option<String>) -> Result<Option<PersistentWallet>, JsError> {
+        let profile = profile::current().await?;
+        let storage = Self::open_storage(&profile, password).await?;
+        Ok(Self::load(&storage)
+            .await?
+            .map(|wallet| PersistentWallet::new(wallet, storage, profile)))
+    }
+
+    /// This methods returns the Wallet stored in string format, that could be parsed into json.
+    ///
+    /// The genesis config is left out, so this can't recreate a wallet: use `export`
+    /// for backups.
+    ///
+    /// If the wallet is encrypted, it is unlocked with `password`; without one, the
+    /// session must already be unlocked.
+    #[wasm_bindgen(js_name = "readJsWallet")]
+    pub async fn read_js_wallet(password: Option<String>) -> Result<String, JsError> {
+        let storage = Self::open_storage(&profile::current().await?, password).await?;
+        let mut fields = storage
+            .read_fields(&["chains", "default", metadata::FIELD])
+            .await?
+            .into_iter();
+        let chains_val = fields
+            .next()
+            .flatten()
+            .unwrap_or_else(|| serde_json::json!({}));
+        let default_val = fields
+            .next()
+            .flatten()
+            .unwrap_or_else(|| serde_json::json!({}));
+        let metadata_val = fields
+            .next()
+            .flatten()
+            .unwrap_or_else(|| serde_json::json!({}));
+
+        // Create combined object
+        let combined = serde_json::json!({
+            "chains": chains_val,
+            "default": default_val,
+            "metadata": metadata_val,
+        });
+
+        // Serialize combined object to a JSON string
+        let combined_str = serde_json::to_string(&combined)
+            .map_err(|e| JsError::new(&format!("Failed to serialize combined wallet: {}", e)))?;
+
+        Ok(combined_str)
+    }
+
+    /// Imports a wallet from JSON, either a backup as written by `export` or a bare
+    /// wallet as returned by `readJsWallet` with an added `genesis` field, and writes it
+    /// to persistent storage.
+    ///
+    /// In `replace` mode the stored wallet is overwritten; in `merge` mode the imported
+    /// chains are added to it, keeping the existing entry for any chain present in
+    /// both and reporting it as a conflict if the entries differ. The imported wallet
+    /// must be for the same network as the stored one.
+    ///
+    /// An encrypted backup is opened with `backup_password`. If the stored wallet is
+    /// encrypted, it is unlocked with `password`; without one, the session must
+    /// already be unlocked.
+    ///
+    /// # Errors
+    /// If the JSON is malformed or inconsistent, if a backup fails verification or
+    /// can't be decrypted, if it is for another network, or if storage is
+    /// inaccessible or locked.
+    #[wasm_bindgen(js_name = "import")]
+    pub async fn import(
+        wallet_json: String,
+        mode: ImportMode,
+        password: Option<String>,
+        backup_password: Option<String>,
+    ) -> Result<ImportReport, JsError> {
+        let wallet_json = if backup::is_backup(&wallet_json) {
+            backup::open(&wallet_json, backup_password.as_deref())?
+        } else {
+            wallet_json
+        };
+        let imported = ImportedWallet::parse(&wallet_json)?;
+        let profile = profile::current().await?;
+        let storage = Self::open_storage(&profile, password).await?;
+        let existing = Self::load(&storage).await?;
+        let (wallet, report) = imported.apply(existing, mode)?;
+        PersistentWallet::new(wallet, storage, profile)
+            .save_to_storage(true)
+            .await?;
+        Ok(report)
+    }
+
+    /// Exports the stored wallet as a self-describing backup, to be restored with
+    /// `import`. The backup is encrypted under `backup_password` if one is given.
+    ///
+    /// If the stored wallet is encrypted, it is unlocked with `password`; without
+    /// one, the session must already be unlocked.
+    ///
+    /// # Errors
+    /// If there is no wallet, or if storage is inaccessible or locked.
+    #[wasm_bindgen(js_name = "export")]
+    pub async fn export(
+        password: Option<String>,
+        backup_password: Option<String>,
+    ) -> Result<String, JsError> {
+        let storage = Self::open_storage(&profile::current().await?, password).await?;
+        let wallet = Self::load(&storage)
+            .await?
+            .ok_or_else(|| JsError::new("There is no wallet to export"))?;
+        backup::export(&wallet, backup_password.as_deref())
+    }
+
+    /// Whether the wallet is encrypted at rest.
+    #[wasm_bindgen(getter, js_name = "isEncrypted")]
+    pub fn is_encrypted(&self) -> bool {
+        self.storage.is_encrypted()
+    }
+
+    /// Encrypts the wallet at rest under `password`, re-encrypting it if it already
+    /// was. A new salt is generated every time.
+    ///
+    /// # Errors
+    /// If the key can't be derived, if the wallet was wiped when the session locked,
+    /// or if it can't be written back.
+    #[wasm_bindgen(js_name = "setPassword")]
+    pub async fn set_password(&mut self, password: String) -> Result<(), JsError> {
+        // Changing the password requires knowing the current one.
+        session::ensure_unlocked_for(&self.profile).await?;
+        // A wiped wallet would overwrite the stored one with no chains.
+        self.ensure_not_wiped()?;
+        let secrets = SecretVault::read_all(&self.profile).await?;
+
+        let (params, key) = KdfParams::generate(&password)?;
+        let key = Rc::new(key);
+        // Everything is sealed with the new key explicitly: the session only switches to
+        // it once the write succeeded, so a failed write leaves the old key in place.
+        let new_key = KeySource::Key(key.clone());
+        let storage = utils::encrypted(&*self.storage, new_key.clone())?;
+        let vault = utils::encrypted(&SecretVault::open(&self.profile).await?, new_key)?;
+        let mut fields = self.fields(true)?;
+        fields.push((
+            crypto::KDF_FIELD.to_string(),
+            utils::kdf_params_value(&params)?,
+        ));
+        // The wallet and the secrets are re-encrypted together, so that an interrupted
+        // password change doesn't leave them under different keys.
+        utils::write_atomically(vec![(&*storage, fields), (&*vault, secrets)]).await?;
+        session::set_key(&self.profile.id, key);
+        if !self.storage.is_encrypted() {
+            self.wallet.wipe_on_lock();
+        }
+        self.storage =
+            utils::encrypted(&*self.storage, KeySource::Session(self.profile.id.clone()))?;
+        Ok(())
+    }
+
+    /// Returns the metadata of `chain`, given by chain ID or label.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet.
+    #[wasm_bindgen(js_name = "chainMetadata")]
+    pub fn chain_metadata(&self, chain: String) -> Result<ChainMetadata, JsError> {
+        let chain_id = self.wallet.resolve_owned(&chain)?;
+        Ok(self
+            .wallet
+            .metadata
+            .borrow()
+            .get(&chain_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Replaces the metadata of `chain`, given by chain ID or label, and saves the
+    /// wallet. `createdVia` is kept as recorded by the wallet.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet, if the label is invalid or used by another
+    /// chain, or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = "setChainMetadata")]
+    pub async fn set_chain_metadata(
+        &self,
+        chain: String,
+        metadata: ChainMetadata,
+    ) -> Result<(), JsError> {
+        let chain_id = self.wallet.resolve_owned(&chain)?;
+        {
+            let mut all = self.wallet.metadata.borrow_mut();
+            if let Some(label) = &metadata.label {
+                metadata::check_label(&all, chain_id, label)?;
+            }
+            let entry = all.entry(chain_id).or_default();
+            *entry = ChainMetadata {
+                created_via: entry.created_via,
+                ..metadata
+            };
+        }
+        self.save_to_storage(false).await
+    }
+
+    /// Resolves `chain`, either a chain ID or the label of a chain of the wallet, to a
+    /// chain ID.
+    ///
+    /// # Errors
+    /// If `chain` is neither a chain ID nor a known label.
+    #[wasm_bindgen(js_name = "resolveChain")]
+    pub fn resolve_chain(&self, chain: String) -> Result<String, JsError> {
+        Ok(self.wallet.resolve(&chain)?.to_string())
+    }
+
+    /// Sets the owner of a chain (the account used to sign blocks on this chain) and
+    /// saves the wallet.
+    ///
+    /// This only changes the wallet entry, e.g. after the chain's owners were changed
+    /// elsewhere: use `Chain.rotateKey` to replace the owner on the chain itself.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet, or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = "setOwner")]
+    pub async fn set_owner(&self, chain: String, owner: AccountOwner) -> Result<(), JsError> {
+        let chain_id = self.wallet.resolve_owned(&chain)?;
+        self.wallet
+            .chains
+            .mutate(chain_id, |chain| chain.owner = Some(owner));
+        self.save_to_storage(false).await
+    }
+
+    /// Makes `chain`, given by chain ID or label, the default chain and saves the
+    /// wallet.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet, or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = "setDefault")]
+    pub async fn set_default(&self, chain: String) -> Result<(), JsError> {
+        let chain_id = self.wallet.resolve_owned(&chain)?;
+        self.wallet.set_default(Some(chain_id));
+        self.save_to_storage(false).await
+    }
+
+    /// Removes `chain`, given by chain ID or label, from the wallet along with its
+    /// metadata, and saves the wallet. If it was the default chain, there is no default
+    /// chain afterwards.
+    ///
+    /// A running `Client` keeps listening to the chain: use `Client.unassign` instead.
+    ///
+    /// # Errors
+    /// If `chain` isn't in the wallet, or if the wallet can't be saved.
+    #[wasm_bindgen(js_name = "forget")]
+    pub async fn forget(&self, chain: String) -> Result<(), JsError> {
+        let chain_id = self.wallet.resolve_owned(&chain)?;
+        self.wallet.forget(chain_id);
+        self.save_to_storage(false).await
+    }
+
+    /// Writes the wallet to persistent storage, including the genesis config if
+    /// `gn_flag` is set.
+    ///
+    /// # Errors
+    /// If storage is inaccessible or locked, or if the wallet was wiped when the
+    /// session locked.
+    pub async fn save_to_storage(&self, gn_flag: bool) -> Result<(), JsError> {
+        self.ensure_not_wiped()?;
+        let fields = self.fields(gn_flag)?;
+        let result = self.storage.write_fields(fields).await;
+        Ok(result?)
+    }
+}
+
+impl PersistentWallet {
+    /// Reads the wallet from an opened store, or `None` if there is no wallet.
+    async fn load(storage: &dyn WalletStorage) -> Result<Option<Wallet>, JsError> {
+        let mut fields = storage
+            .read_fields(&["chains", "default", "genesis", metadata::FIELD])
+            .await?
+            .into_iter();
+        let chains_result = fields.next().flatten();
+        let default_result = fields.next().flatten();
+        let genesis_result = fields.next().flatten();
+        let metadata = metadata::parse(fields.next().flatten())?;
+
+        let chains_val = match chains_result {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let genesis_val = match genesis_result {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+
+        let chains: HashMap<ChainId, Chain> = serde_json::from_value(chains_val)
+            .map_err(|e| JsError::new(&format!("Failed to deserialize chains: {e}")))?;
+
+        // Genesis: stored as JSON string
+        let genesis_json = genesis_val
+            .as_str()
+            .ok_or_else(|| JsError::new("Failed to deserialize genesis: expected a string"))?;
+        let genesis_config: GenesisConfig = serde_json::from_str(genesis_json)
+            .map_err(|e| JsError::new(&format!("Failed to deserialize genesis: {e}")))?;
+
+        // Default: null only when the wallet is new and no chain exists
+        let default: Option<ChainId> = match default_result {
+            Some(val) => serde_json::from_value(val)
+                .map_err(|e| JsError::new(&format!("Failed to deserialize default: {e}")))?,
+            None => None,
+        };
+
+        let mut memory = wallet::Memory::default();
+        memory.extend(chains.iter().map(|(id, chain)| (*id, chain.clone())));
+
+        Ok(Some(Wallet {
+            chains: crate::WalletChains::new(memory),
+            default: Rc::new(Cell::new(default)),
+            default_hooks: Rc::default(),
+            genesis_config,
+            metadata: Rc::new(RefCell::new(metadata)),
+            wiped: Rc::default(),
+            lock_hook: Rc::default(),
+        }))
+    }
+
+    /// Opens the wallet store of `profile`, unlocking the session with `password` if it is
+    /// encrypted, and migrates the wallet to the current layout.
+    async fn open_storage(
+        profile: &Profile,
+        password: Option<String>,
+    ) -> Result<Rc<dyn WalletStorage>, JsError> {
+        let storage = schema::storage_for(profile);
+        let storage: Rc<dyn WalletStorage> = match storage.read_kdf_params().await? {
+            Some(params) => {
+                match password {
+                    Some(password) => {
+                        session::set_key(&profile.id, Rc::new(params.unlock(&password)?));
+                    }
+                    None => session::ensure_unlocked_for(profile).await?,
+                }
+                utils::encrypted(&storage, KeySource::Session(profile.id.clone()))?
+            }
+            None => Rc::new(storage),
+        };
+        schema::migrate(&*storage).await?;
+        Ok(storage)
+    }
+
+    /// Fails if the wallet was wiped from memory when the session locked, so that it
+    /// isn't written back empty.
+    fn ensure_not_wiped(&self) -> Result<(), JsError> {
+        if self.wallet.wiped.get() {
+            return Err(JsError::new(
+                "The wallet was wiped when the session locked: read it again with Wallet.get",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serializes the wallet into the fields written to storage, including the
+    /// genesis config if `gn_flag` is set.
+    fn fields(&self, gn_flag: bool) -> Result<Vec<(String, serde_json::Value)>, JsError> {
+        let chains_value = serde_json::to_value(&*self.wallet.chains)
+            .map_err(|e| JsError::new(&format!("Failed to serialize chains: {}", e)))?;
+        let default_value = serde_json::to_value(self.wallet.default.get())
+            .map_err(|e| JsError::new(&format!("Failed to serialize default: {}", e)))?;
+        let metadata_value = serde_json::to_value(&*self.wallet.metadata.borrow())
+            .map_err(|e| JsError::new(&format!("Failed to serialize chain metadata: {e}")))?;
+        let mut fields = vec![
+            schema::version_field(),
+            ("chains".to_string(), chains_value),
+            ("default".to_string(), default_value),
+            (metadata::FIELD.to_string(), metadata_value),
+        ];
+
+        if gn_flag {
+            let genesis_json = serde_json::to_string(&self.wallet.genesis_config)
+                .map_err(|e| JsError::new(&format!("Failed to serialize genesis: {}", e)))?;
+            fields.push(("genesis".to_string(), genesis_json.into()));
+        }
+        Ok(fields)
+    }
+}
+
+/// A wallet that stores the user's chains and keys in memory.
+#[derive(Clone)]
+pub struct Wallet {
+    pub(crate) chains: crate::WalletChains,
+    /// The default chain, shared between clones like `chains`.
+    pub(crate) default: Rc<Cell<Option<ChainId>>>,
+    /// Run whenever the default chain is changed with `set_default`, shared between
+    /// clones like `chains`.
+    default_hooks: Rc<RefCell<Vec<Box<dyn Fn()>>>>,
+    pub(crate) genesis_config: GenesisConfig,
+    /// Wallet-side metadata about the chains, shared between clones like `chains`.
+    pub(crate) metadata: Rc<RefCell<metadata::Metadata>>,
+    /// Set once the wallet was wiped from memory when the session locked.
+    pub(crate) wiped: Rc<Cell<bool>>,
+    /// The hook wiping the wallet when the session locks, if it is encrypted, shared
+    /// between clones so that it is unregistered with the last of them.
+    pub(crate) lock_hook: Rc<RefCell<Option<session::LockHookHandle>>>,
+}
+
+impl Wallet {
+    /// Creates a wallet without chains for the network of `genesis_config`.
+    pub(crate) fn empty(genesis_config: GenesisConfig) -> Self {
+        Self {
+            chains: crate::WalletChains::new(wallet::Memory::default()),
+            default: Rc::default(),
+            default_hooks: Rc::default(),
+            genesis_config,
+            metadata: Rc::default(),
+            wiped: Rc::default(),
+            lock_hook: Rc::default(),
+        }
+    }
+
+    /// Wipes the chains, the default chain and the metadata from memory, for every
+    /// clone of the wallet and the clients using it, whenever the session locks.
+    ///
+    /// The hook only holds weak references, so it doesn't keep the wallet alive.
+    pub(crate) fn wipe_on_lock(&self) {
+        let chains = crate::WalletChains::downgrade(&self.chains);
+        let default = Rc::downgrade(&self.default);
+        let metadata = Rc::downgrade(&self.metadata);
+        let wiped = Rc::downgrade(&self.wiped);
+        let hook = session::on_lock(move || {
+            if let Some(wiped) = wiped.upgrade() {
+                wiped.set(true);
+            }
+            if let Some(chains) = chains.upgrade() {
+                for chain_id in chains.chain_ids() {
+                    chains.remove(chain_id);
+                }
+            }
+            if let Some(default) = default.upgrade() {
+                default.set(None);
+            }
+            if let Some(metadata) = metadata.upgrade() {
+                metadata.borrow_mut().clear();
+            }
+        });
+        *self.lock_hook.borrow_mut() = Some(hook);
+    }
+
+    /// Resolves `chain`, either a chain ID or the label of a chain of the wallet, to a
+    /// chain ID.
+    ///
+    /// # Errors
+    /// If `chain` is neither a chain ID nor a known label.
+    pub(crate) fn resolve(&self, chain: &str) -> JsResult<ChainId> {
+        metadata::resolve(&self.metadata.borrow(), chain)
+    }
+
+    /// Like [`Wallet::resolve`], but also checks that the chain is in the wallet.
+    ///
+    /// # Errors
+    /// If `chain` doesn't resolve to a chain of the wallet.
+    pub(crate) fn resolve_owned(&self, chain: &str) -> JsResult<ChainId> {
+        let chain_id = self.resolve(chain)?;
+        if self.chains.get(chain_id).is_none() {
+            return Err(JsError::new(&format!(
+                "Chain {chain_id} is not in the wallet"
+            )));
+        }
+        Ok(chain_id)
+    }
+
+    /// The owner the wallet signs for `chain_id` with, if any.
+    pub(crate) fn owner(&self, chain_id: ChainId) -> Option<AccountOwner> {
+        self.chains.get(chain_id).and_then(|chain| chain.owner)
+    }
+
+    /// Removes `chain_id` and its metadata from the wallet, unsetting the default chain
+    /// if it was that one.
+    pub(crate) fn forget(&self, chain_id: ChainId) {
+        self.chains.remove(chain_id);
+        self.metadata.borrow_mut().remove(&chain_id);
+        if self.default.get() == Some(chain_id) {
+            self.set_default(None);
+        }
+    }
+
+    /// Sets the default chain, and runs the hooks registered with `on_default_change`.
+    pub(crate) fn set_default(&self, default: Option<ChainId>) {
+        self.default.set(default);
+        for hook in self.default_hooks.borrow().iter() {
+            hook();
+        }
+    }
+
+    /// Registers a hook run whenever the default chain is changed with `set_default`.
+    pub(crate) fn on_default_change(&self, hook: impl Fn() + 'static) {
+        self.default_hooks.borrow_mut().push(Box::new(hook));
+    }
+
+    /// Records how `chain_id` was added, unless that is already known.
+    pub(crate) fn set_created_via(&self, chain_id: ChainId, created_via: CreatedVia) {
+        self.metadata
+            .borrow_mut()
+            .entry(chain_id)
+            .or_default()
+            .created_via
+            .get_or_insert(created_via);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use linera_base::data_types::{Amount, Timestamp};
+    use linera_client::config::CommitteeConfig;
+    use linera_execution::ResourceControlPolicy;
+
+    use super::*;
+    use crate::signer::KeyScheme;
+
+    fn genesis_config(network_name: &str) -> GenesisConfig {
+        let admin = KeyScheme::Ed25519.secret_key(&[7; 32]).unwrap();
+        GenesisConfig::new(
+            CommitteeConfig {
+                validators: Vec::new(),
+            },
+            Timestamp::from(0),
+            ResourceControlPolicy::default(),
+            network_name.to_string(),
+            admin.public(),
+            Amount::from_tokens(10),
+        )
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            id: "test".to_string(),
+            name: "test".to_string(),
+        }
+    }
+
+    /// A wallet holding the admin chain of the test network, as its default chain.
+    fn test_wallet() -> (Wallet, ChainId) {
+        let genesis_config = genesis_config("test");
+        let description = genesis_config.chains[0].clone();
+        let chain_id = description.id();
+        let wallet = Wallet::empty(genesis_config);
+        wallet.chains.insert(chain_id, description.into());
+        wallet.default.set(Some(chain_id));
+        wallet
+            .metadata
+            .borrow_mut()
+            .entry(chain_id)
+            .or_default()
+            .label = Some("main".to_string());
+        (wallet, chain_id)
+    }
+
+    fn to_json(value: &impl serde::Serialize) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn wallet_round_trips_through_storage() {
+        let storage = MemoryStorage::new();
+        let (wallet, chain_id) = test_wallet();
+        let persistent = PersistentWallet::new(wallet.clone(), Rc::new(storage.clone()), profile());
+        block_on(persistent.save_to_storage(true)).unwrap();
+
+        let read = block_on(PersistentWallet::open(storage, profile()))
+            .unwrap()
+            .expect("the wallet was written");
+        assert_eq!(to_json(&*read.wallet.chains), to_json(&*wallet.chains));
+        assert_eq!(read.wallet.default.get(), Some(chain_id));
+        assert_eq!(
+            to_json(&*read.wallet.metadata.borrow()),
+            to_json(&*wallet.metadata.borrow())
+        );
+        assert_eq!(
+            read.wallet.genesis_config.hash(),
+            wallet.genesis_config.hash()
+        );
+    }
+
+    #[test]
+    fn empty_storage_holds_no_wallet() {
+        let read = block_on(PersistentWallet::open(MemoryStorage::new(), profile())).unwrap();
+        assert!(read.is_none());
+    }
+
+    #[test]
+    fn unversioned_wallet_is_migrated() {
+        let storage = MemoryStorage::new();
+        let (wallet, chain_id) = test_wallet();
+        // The v1 layout: no version record, the genesis config as an object and the
+        // default chain as a bare chain ID.
+        block_on(storage.write_fields(vec![
+            ("chains".to_string(), to_json(&*wallet.chains)),
+            ("genesis".to_string(), to_json(&wallet.genesis_config)),
+            ("default".to_string(), chain_id.to_string().into()),
+        ]))
+        .unwrap();
+
+        let read = block_on(PersistentWallet::open(storage.clone(), profile()))
+            .unwrap()
+            .expect("the wallet was written");
+        assert_eq!(read.wallet.default.get(), Some(chain_id));
+        assert_eq!(
+            read.wallet.genesis_config.hash(),
+            wallet.genesis_config.hash()
+        );
+        assert_eq!(
+            block_on(schema::read_version(&storage)).unwrap(),
+            Some(schema::LAYOUT_VERSION)
+        );
+        assert!(block_on(storage.read_field("genesis"))
+            .unwrap()
+            .is_some_and(|genesis| genesis.is_string()));
+    }
+
+    #[cfg(not(feature = "web"))]
+    #[test]
+    fn set_password_on_a_wiped_wallet_keeps_the_stored_one() {
+        FileStorage::use_test_data_dir();
+        let profile = Profile {
+            id: "wiped".to_string(),
+            name: "wiped".to_string(),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        // Unlocking the session spawns the idle watcher, a local task.
+        tokio::task::LocalSet::new().block_on(&runtime, async {
+            let storage = schema::storage_for(&profile);
+            let (wallet, chain_id) = test_wallet();
+            let mut persistent =
+                PersistentWallet::new(wallet, Rc::new(storage.clone()), profile.clone());
+            persistent.save_to_storage(true).await.unwrap();
+            persistent.set_password("first".to_string()).await.unwrap();
+
+            session::lock();
+            let params = storage.read_kdf_params().await.unwrap().unwrap();
+            session::set_key(&profile.id, Rc::new(params.unlock("first").unwrap()));
+            assert!(persistent.set_password("second".to_string()).await.is_err());
+
+            let encrypted =
+                utils::encrypted(&storage, KeySource::Session(profile.id.clone())).unwrap();
+            let read = PersistentWallet::load(&*encrypted)
+                .await
+                .unwrap()
+                .expect("the wallet was written");
+            assert!(read.chains.get(chain_id).is_some());
+            assert_eq!(read.default.get(), Some(chain_id));
+            std::fs::remove_dir_all(storage.path().parent().unwrap()).unwrap();
+        });
+    }
+
+    #[test]
+    fn owner_changes_are_seen_by_every_handle() {
+        let (wallet, chain_id) = test_wallet();
+        // Each `Chain` handle reaches the wallet through its own clone of the client.
+        let first = PersistentWallet::new(wallet, Rc::new(MemoryStorage::new()), profile());
+        let second = first.clone();
+        assert_eq!(second.wallet.owner(chain_id), None);
+
+        let owner = AccountOwner::from(KeyScheme::Ed25519.secret_key(&[1; 32]).unwrap().public());
+        first
+            .wallet
+            .chains
+            .mutate(chain_id, |chain| chain.owner = Some(owner));
+        assert_eq!(second.wallet.owner(chain_id), Some(owner));
+    }
+
+    #[test]
+    fn encrypted_backup_round_trips() {
+        let (wallet, chain_id) = test_wallet();
+        let json = backup::export(&wallet, Some("password")).unwrap();
+        assert!(backup::is_backup(&json));
+        assert!(backup::open(&json, Some("wrong")).is_err());
+
+        let contents = backup::open(&json, Some("password")).unwrap();
+        let imported = ImportedWallet::parse(&contents).unwrap();
+        let (read, _) = imported.apply(None, ImportMode::Replace).unwrap();
+        assert_eq!(to_json(&*read.chains), to_json(&*wallet.chains));
+        assert_eq!(read.default.get(), Some(chain_id));
+    }
+
+    #[test]
+    fn backup_of_another_format_is_rejected() {
+        let (wallet, _) = test_wallet();
+        let mut backup: serde_json::Value =
+            serde_json::from_str(&backup::export(&wallet, None).unwrap()).unwrap();
+        backup["format"] = "something-else".into();
+        assert!(backup::open(&backup.to_string(), None).is_err());
+    }
+
+    /// Returns the JSON of a wallet holding `chain_id` with `owner`, for the network of
+    /// `genesis_config`.
+    fn import_json(
+        genesis_config: &GenesisConfig,
+        chain_id: ChainId,
+        owner: AccountOwner,
+    ) -> String {
+        let description = genesis_config.chains[0].clone();
+        let chain = Chain {
+            owner: Some(owner),
+            ..description.into()
+        };
+        serde_json::json!({
+            "chains": { (chain_id.to_string()): chain },
+            "genesis": serde_json::to_string(genesis_config).unwrap(),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn merge_import_keeps_conflicting_entries() {
+        let (existing, chain_id) = test_wallet();
+        let owner = AccountOwner::from(KeyScheme::Ed25519.secret_key(&[1; 32]).unwrap().public());
+        let imported =
+            ImportedWallet::parse(&import_json(&existing.genesis_config, chain_id, owner)).unwrap();
+
+        let (wallet, report) = imported
+            .apply(Some(existing.clone()), ImportMode::Merge)
+            .unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].chain_id, chain_id);
+        assert!(report.imported.is_empty());
+        assert_eq!(wallet.chains.get(chain_id).unwrap().owner, None);
+        assert_eq!(wallet.default.get(), Some(chain_id));
+    }
+
+    #[test]
+    fn replace_import_overwrites_entries() {
+        let (existing, chain_id) = test_wallet();
+        let owner = AccountOwner::from(KeyScheme::Ed25519.secret_key(&[1; 32]).unwrap().public());
+        let imported =
+            ImportedWallet::parse(&import_json(&existing.genesis_config, chain_id, owner)).unwrap();
+
+        let (wallet, report) = imported.apply(Some(existing), ImportMode::Replace).unwrap();
+        assert_eq!(report.imported, vec![chain_id]);
+        assert_eq!(wallet.chains.get(chain_id).unwrap().owner, Some(owner));
+        assert!(wallet.metadata.borrow().is_empty());
+    }
+
+    #[test]
+    fn import_from_another_network_is_rejected() {
+        let (existing, _) = test_wallet();
+        let other = genesis_config("other");
+        let chain_id = other.chains[0].id();
+        let owner = AccountOwner::from(KeyScheme::Ed25519.secret_key(&[1; 32]).unwrap().public());
+        let imported = ImportedWallet::parse(&import_json(&other, chain_id, owner)).unwrap();
+
+        assert!(imported.apply(Some(existing), ImportMode::Merge).is_err());
+    }
+}
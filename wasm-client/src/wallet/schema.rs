@@ -0,0 +1,182 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The layout of the wallet in persistent storage, and the migrations between its
+//! versions.
+//!
+//! The wallet is stored as one [`WalletStorage`] record per field. The layout version is kept
+//! in the [`VERSION_FIELD`] record and checked every time the wallet is opened: older
+//! layouts are brought up to [`LAYOUT_VERSION`] by running [`MIGRATIONS`] in order.
+//!
+//! - v1 (unversioned): `chains` as serialized by `serde_wasm_bindgen`, `genesis` as
+//!   either a JSON string or a JS object, and `default` as either a bare chain ID
+//!   string or a serialized value.
+//! - v2: `genesis` is always a JSON string, and `default` is always a serialized
+//!   `Option<ChainId>`.
+
+use futures::future::{FutureExt as _, LocalBoxFuture};
+use linera_base::identifiers::ChainId;
+use linera_client::config::GenesisConfig;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    profile::{self, Profile},
+    utils::{self, PlatformStorage, WalletStorage},
+    JsError, JsResult,
+};
+
+/// The name of the IndexedDB database holding the wallet of the default profile.
+pub const DB_NAME: &str = "linera";
+/// The object store holding the wallet fields.
+pub const STORE_NAME: &str = "ldb";
+/// The object store holding the Secret vault, kept in the wallet database so that
+/// both can be written in one transaction.
+pub const VAULT_STORE_NAME: &str = "vault";
+/// Every object store of the wallet database.
+const STORES: &[&str] = &[STORE_NAME, VAULT_STORE_NAME];
+/// The version of the IndexedDB database itself, i.e. of its set of object stores.
+/// This is unrelated to the layout of the wallet fields. Version 3 added the vault.
+pub const DB_VERSION: u32 = 3;
+
+/// The field holding the layout version.
+pub const VERSION_FIELD: &str = "version";
+/// The version of the layout written by this code.
+pub const LAYOUT_VERSION: u32 = 2;
+
+type Migration = for<'a> fn(&'a dyn WalletStorage) -> LocalBoxFuture<'a, JsResult<()>>;
+
+/// `MIGRATIONS[i]` upgrades a wallet from layout version `i + 1` to `i + 2`.
+const MIGRATIONS: &[Migration] = &[|storage| v1_to_v2(storage).boxed_local()];
+
+/// Returns a handle on the (unencrypted) wallet store of `profile`.
+pub fn storage_for(profile: &Profile) -> PlatformStorage {
+    utils::open_store_in(&profile.wallet_db(), STORES, STORE_NAME, DB_VERSION)
+}
+
+/// Returns a handle on the (unencrypted) Secret vault store of `profile`.
+///
+/// Use `SecretVault::open`, which first moves secrets out of their legacy database.
+pub fn vault_storage_for(profile: &Profile) -> PlatformStorage {
+    utils::open_store_in(&profile.wallet_db(), STORES, VAULT_STORE_NAME, DB_VERSION)
+}
+
+/// Returns a handle on the (unencrypted) wallet store of the selected profile.
+///
+/// # Errors
+/// If the profile registry is inaccessible.
+pub async fn storage() -> JsResult<PlatformStorage> {
+    Ok(storage_for(&profile::current().await?))
+}
+
+/// Reads the layout version of the stored wallet, or `None` if there is no wallet.
+///
+/// # Errors
+/// If storage is inaccessible or the version record is malformed.
+pub async fn read_version(storage: &dyn WalletStorage) -> JsResult<Option<u32>> {
+    if let Some(version) = storage.read_field(VERSION_FIELD).await? {
+        let version = version
+            .as_f64()
+            .and_then(num_traits::cast)
+            // Layout versions start at 1.
+            .filter(|version: &u32| *version > 0)
+            .ok_or_else(|| JsError::new("Malformed wallet layout version"))?;
+        return Ok(Some(version));
+    }
+    // Wallets written before versioning was introduced have no version record.
+    let fields = storage.read_fields(&["chains", "genesis"]).await?;
+    if fields.iter().any(Option::is_some) {
+        return Ok(Some(1));
+    }
+    Ok(None)
+}
+
+/// The field recording the layout version, to be written along with the wallet.
+pub fn version_field() -> (String, serde_json::Value) {
+    (VERSION_FIELD.to_string(), LAYOUT_VERSION.into())
+}
+
+/// Brings the stored wallet up to [`LAYOUT_VERSION`], if there is one.
+///
+/// Each migration writes its changes together with the new version number in a single
+/// transaction, so an interrupted upgrade resumes from the last completed step.
+///
+/// # Errors
+/// If storage is inaccessible, a migration fails, or the wallet was written by a newer
+/// version of this code.
+pub async fn migrate(storage: &dyn WalletStorage) -> JsResult<()> {
+    let Some(mut version) = read_version(storage).await? else {
+        return Ok(());
+    };
+    if version > LAYOUT_VERSION {
+        return Err(JsError::new(&format!(
+            "The wallet uses layout version {version}, but only versions up to \
+             {LAYOUT_VERSION} are supported: please update"
+        )));
+    }
+    while version < LAYOUT_VERSION {
+        let index = usize::try_from(version - 1).expect("layout versions start at 1");
+        tracing::info!(
+            "migrating wallet layout from v{version} to v{}",
+            version + 1
+        );
+        MIGRATIONS[index](storage).await?;
+        version += 1;
+    }
+    Ok(())
+}
+
+/// Normalizes `genesis` to a JSON string, and `default` to a serialized
+/// `Option<ChainId>`.
+async fn v1_to_v2(storage: &dyn WalletStorage) -> JsResult<()> {
+    let mut fields = vec![(VERSION_FIELD.to_string(), 2u32.into())];
+
+    if let Some(genesis) = storage.read_field("genesis").await? {
+        if !genesis.is_string() {
+            let genesis_config: GenesisConfig = serde_json::from_value(genesis)
+                .map_err(|e| JsError::new(&format!("Failed to deserialize genesis: {e}")))?;
+            let genesis_json = serde_json::to_string(&genesis_config)
+                .map_err(|e| JsError::new(&format!("Failed to serialize genesis: {e}")))?;
+            fields.push(("genesis".to_string(), genesis_json.into()));
+        }
+    }
+
+    let default = match storage.read_field("default").await? {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(chain_id)) => Some(
+            chain_id
+                .parse::<ChainId>()
+                .map_err(|e| JsError::new(&format!("Failed to parse chain ID: {e}")))?,
+        ),
+        Some(value) => serde_json::from_value(value)
+            .map_err(|e| JsError::new(&format!("Failed to deserialize default: {e}")))?,
+    };
+    let default_value = serde_json::to_value(default)
+        .map_err(|e| JsError::new(&format!("Failed to serialize default: {e}")))?;
+    fields.push(("default".to_string(), default_value));
+
+    Ok(storage.write_fields(fields).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::wallet::MemoryStorage;
+
+    #[test]
+    fn layout_version_zero_is_malformed() {
+        let storage = MemoryStorage::new();
+        block_on(storage.write_field(VERSION_FIELD, 0.into())).unwrap();
+        assert!(block_on(read_version(&storage)).is_err());
+        assert!(block_on(migrate(&storage)).is_err());
+    }
+
+    #[test]
+    fn unversioned_wallet_is_v1() {
+        let storage = MemoryStorage::new();
+        assert_eq!(block_on(read_version(&storage)).unwrap(), None);
+        block_on(storage.write_field("chains", serde_json::json!({}))).unwrap();
+        assert_eq!(block_on(read_version(&storage)).unwrap(), Some(1));
+    }
+}
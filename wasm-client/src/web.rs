@@ -0,0 +1,218 @@
+// Copyright (c) Zefchain Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The JS side of APIs whose core returns Rust values: the conversions from and to
+//! `JsValue`, and the wrapping of JS callbacks into Rust closures.
+
+use std::rc::Rc;
+
+use futures::future::FutureExt as _;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    chain::{approval::ApprovalHook, Chain, NotificationHandle},
+    session::{self, JsSession, LockHookHandle},
+    signer::{self, DiscoveryOptions, InMemorySigner},
+    storage::ChainStorage,
+    utils::SecretVault,
+    wallet::PersistentWallet,
+    Client, JsError, JsResult,
+};
+
+/// Converts `value` to a plain JS value, with maps as objects and 64-bit integers as
+/// numbers.
+fn to_js(value: &impl Serialize) -> JsResult<JsValue> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+#[wasm_bindgen(js_class = "Secret")]
+impl SecretVault {
+    /// Stores a secret value under the provided field name.
+    /// Returns a JS Promise that resolves when the value has been written.
+    ///
+    /// Secrets are encrypted under the session key if the wallet is password-protected.
+    ///
+    /// # Errors
+    /// If the value isn't JSON-compatible, the wallet is locked or storage is
+    /// inaccessible.
+    #[wasm_bindgen(js_name = "set")]
+    pub async fn js_set(&self, field: String, value: JsValue) -> JsResult<()> {
+        self.set(&field, serde_wasm_bindgen::from_value(value)?)
+            .await
+    }
+
+    /// Retrieves a secret value for the provided field name.
+    /// Returns `undefined` if the key is not present.
+    ///
+    /// # Errors
+    /// If the wallet is locked or storage is inaccessible.
+    #[wasm_bindgen(js_name = "get")]
+    pub async fn js_get(&self, field: String) -> JsResult<JsValue> {
+        match self.get(&field).await? {
+            Some(value) => to_js(&value),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "Wallet")]
+impl PersistentWallet {
+    /// Lists the chains of the wallet with their metadata, as an array of
+    /// `ChainEntry`, leaving out hidden chains unless `include_hidden` is set.
+    ///
+    /// # Errors
+    /// If the list can't be serialized.
+    #[wasm_bindgen(js_name = "listChains")]
+    pub fn list_chains(&self, include_hidden: Option<bool>) -> JsResult<JsValue> {
+        to_js(&self.chain_entries(include_hidden.unwrap_or(false)))
+    }
+}
+
+#[wasm_bindgen]
+impl Chain {
+    /// Lists the message bundles waiting in the inbox of the chain, oldest first, as an
+    /// array of `ReceivedBundle`.
+    ///
+    /// # Errors
+    /// If the chain state can't be read.
+    #[wasm_bindgen(js_name = pendingMessages)]
+    pub async fn pending_messages(&self) -> JsResult<JsValue> {
+        to_js(&self.pending_bundles().await?)
+    }
+
+    /// Sets a callback to be called when a notification is received
+    /// from the network.
+    ///
+    /// # Errors
+    /// If we fail to subscribe to the notification stream.
+    ///
+    /// # Panics
+    /// If the handler function fails.
+    #[wasm_bindgen(js_name = onNotification)]
+    pub fn on_notification(&self, handler: js_sys::Function) -> JsResult<NotificationHandle> {
+        self.on_new_block(move |notification| {
+            handler
+                .call1(
+                    &JsValue::null(),
+                    &serde_wasm_bindgen::to_value(notification).unwrap(),
+                )
+                .unwrap_throw();
+        })
+    }
+
+    /// Gets the version information of the validators of the current network.
+    ///
+    /// # Errors
+    /// If a validator is unreachable.
+    #[wasm_bindgen(js_name = validatorVersionInfo)]
+    pub async fn validator_version_info(&self) -> JsResult<JsValue> {
+        Ok(self.validator_versions().await?.serialize(
+            &serde_wasm_bindgen::Serializer::new()
+                .serialize_large_number_types_as_bigints(true)
+                .serialize_maps_as_objects(true),
+        )?)
+    }
+}
+
+#[wasm_bindgen]
+impl InMemorySigner {
+    /// Finds the owners derived from the mnemonic that own chains, adding their keys
+    /// to the signer, and returns them as an array of `DiscoveredOwner`.
+    ///
+    /// Owners are derived at successive indices for each scheme, until `gapLimit`
+    /// consecutive ones own none of the chains.
+    ///
+    /// # Errors
+    /// If the signer has no mnemonic, if the session is locked, or if the keys or the
+    /// wallet can't be saved.
+    pub async fn discover(
+        &self,
+        client: &Client,
+        options: Option<DiscoveryOptions>,
+    ) -> JsResult<JsValue> {
+        to_js(&self.discover_owners(client, options).await?)
+    }
+}
+
+#[wasm_bindgen]
+impl Client {
+    /// Sets the function approving blocks before they are signed, or removes it if
+    /// `hook` is `null`.
+    ///
+    /// The hook is called with a `PendingBlock`, and returns, or resolves to, whether
+    /// to sign it. If it doesn't approve it, the call proposing the block fails with a
+    /// `SignerError` of kind `userRejected`; if it throws, with a `SignerError` of
+    /// kind `js` carrying the error.
+    ///
+    /// If the block about to be signed differs from the one approved, e.g. because
+    /// messages arrived meanwhile, the hook is called again with it, while the block
+    /// is being proposed: it shouldn't wait on other calls proposing blocks.
+    #[wasm_bindgen(js_name = "setApprovalHook")]
+    pub fn js_set_approval_hook(&self, hook: Option<js_sys::Function>) {
+        let hook = hook.map(|hook| -> ApprovalHook {
+            Rc::new(move |block| {
+                let hook = hook.clone();
+                async move {
+                    let approved = hook
+                        .call1(
+                            &JsValue::NULL,
+                            &to_js(&block).map_err(|_| signer::Error::JsConversion)?,
+                        )
+                        .map_err(signer::Error::from)?;
+                    let approved = match approved.dyn_into::<js_sys::Promise>() {
+                        Ok(promise) => wasm_bindgen_futures::JsFuture::from(promise)
+                            .await
+                            .map_err(signer::Error::from)?,
+                        Err(approved) => approved,
+                    };
+                    Ok(approved.is_truthy())
+                }
+                .boxed_local()
+            })
+        });
+        self.set_approval_hook(hook);
+    }
+}
+
+#[wasm_bindgen(js_class = "Session")]
+impl JsSession {
+    /// Registers a callback to be called whenever the session locks, so that the
+    /// caller can drop any decrypted material it holds (e.g. a signer), until the
+    /// returned handle is unregistered.
+    #[wasm_bindgen(js_name = "onLock")]
+    pub fn js_on_lock(callback: js_sys::Function) -> LockHookHandle {
+        session::on_lock(move || {
+            if let Err(error) = callback.call0(&JsValue::NULL) {
+                tracing::warn!("session lock hook failed: {error:?}");
+            }
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl ChainStorage {
+    /// Returns the estimated number of bytes of storage used by the whole extension,
+    /// including wallets and other profiles.
+    ///
+    /// # Errors
+    /// If the storage estimate is unavailable.
+    #[wasm_bindgen(js_name = originUsage)]
+    pub async fn origin_usage() -> JsResult<f64> {
+        let storage = web_sys::window()
+            .ok_or_else(|| JsError::new("No window available"))?
+            .navigator()
+            .storage();
+        let estimate = wasm_bindgen_futures::JsFuture::from(
+            storage
+                .estimate()
+                .map_err(|_| JsError::new("Storage estimate unavailable"))?,
+        )
+        .await
+        .map_err(|_| JsError::new("Storage estimate failed"))?;
+        js_sys::Reflect::get(&estimate, &"usage".into())
+            .ok()
+            .and_then(|usage| usage.as_f64())
+            .ok_or_else(|| JsError::new("Storage estimate unavailable"))
+    }
+}
@@ -1,6 +1,5 @@
 import type * as wasmType from '@linera/wasm-client'
-import { Chain, Client, Wallet } from '@linera/wasm-client'
-import { Signer } from '@linera/wasm-client'
+import { Chain, Client, InMemorySigner, Wallet } from '@linera/wasm-client'
 
 export type Request = {
   type: 'QUERY'
@@ -33,7 +32,7 @@ export class ClientManager {
   async init(
     wasmInstance: typeof wasmType,
     wallet: Wallet,
-    signer: Signer
+    signer: InMemorySigner
   ): Promise<void> {
     if (this.activeChain) {
       return
@@ -44,7 +43,7 @@ export class ClientManager {
     }
 
     try {
-      const client = await new wasmInstance.Client(wallet, signer)
+      const client = await wasmInstance.Client.withInMemorySigner(wallet, signer)
 
       this.client = client
       const chain = await client.chain()
@@ -55,6 +54,19 @@ export class ClientManager {
     }
   }
 
+  /**
+   * Finds the owners derived from the signer's mnemonic that own chains of the
+   * wallet, so that the signer holds their keys, and returns how many there are.
+   */
+  async discover(signer: InMemorySigner): Promise<number> {
+    if (!this.client) {
+      throw new Error('Missing Client')
+    }
+
+    const owners = await signer.discover(this.client)
+    return owners.length
+  }
+
   async initChainClient(chainId: wasmType.ChainId): Promise<Chain> {
     if (!this.client) {
       throw new Error('Missing Client')
@@ -2,7 +2,7 @@ import * as wasm from '@linera/wasm-client'
 import * as guard from './message.guard'
 import { WasmManager } from './wasmManager'
 import { ClientManager } from './clientManager'
-import { WalletManager } from './walletManager'
+import { FAUCET_URL, WalletManager } from './walletManager'
 import { ApprovalManager, type SensitiveAction } from './approvalManager'
 
 type Result<T> = { success: true; data: T } | { success: false; error: string }
@@ -225,7 +225,7 @@ export class Server {
         // Execute the approved action
         switch (request.type) {
           case 'CONNECT_WALLET':
-            request.wrap(this.wallet.getSigner().address())
+            request.wrap(this.wallet.getOwner())
             break
           case 'ASSIGNMENT':
             await this._handleAssignment(
@@ -415,11 +415,11 @@ export class Server {
   faucetHandlers: Record<OpType, FaucetHandler> = {
     CREATE_WALLET: async (faucet) => {
       const wallet = await faucet.createWallet()
-      this.wallet.create(wallet)
+      await this.wallet.create(wallet)
 
       let chainId = await faucet.claimChain(
         wallet,
-        this.wallet.getSigner().address()
+        this.wallet.getOwner()
       )
 
       return { success: true, data: chainId }
@@ -429,7 +429,7 @@ export class Server {
         success: true,
         data: await faucet.claimChain(
           this.wallet.getWallet(),
-          this.wallet.getSigner().address()
+          this.wallet.getOwner()
         ),
       }
     },
@@ -441,8 +441,6 @@ export class Server {
    * and also to set wallet in indexeddb
    */
   private async faucetAction(op: OpType): Promise<Result<string>> {
-    // const FAUCET_URL = 'http://localhost:8079'
-    const FAUCET_URL = 'https://faucet.testnet-conway.linera.net/'
     const faucet = new wasm.Faucet(FAUCET_URL)
     const handler = this.faucetHandlers[op]
     if (!handler) return { success: false, error: 'Invalid operation' }
@@ -537,7 +535,10 @@ export class Server {
   ) {
     try {
       const result = await this.wallet.setWallet(message.wallet)
-      wrap(result)
+      // The imported chains may be owned by keys derived at other indices
+      await this._initClient()
+      const owners = await this.client.discover(this.wallet.getSigner())
+      wrap(`${result}, ${owners} owner(s) found`)
     } catch (err) {
       console.error(err)
     }
@@ -604,7 +605,7 @@ export class Server {
       // set this to make sure we don't process any message at this time
       this.isReady = false
       const { chainId } = message
-      const owner = this.wallet.getSigner().address()
+      const owner = this.wallet.getOwner()
 
       const result = await this.client.assign(chainId, owner) // assign chain in wallet manager, this will also reinitialize wallet
       wrap(result)
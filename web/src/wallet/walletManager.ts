@@ -1,11 +1,15 @@
-import type { Wallet } from '@linera/wasm-client'
+import type { InMemorySigner, Wallet } from '@linera/wasm-client'
 import type * as wasmType from '@linera/wasm-client'
-import { PrivateKeySigner } from '@linera/wasm-client'
+
+// export const FAUCET_URL = 'http://localhost:8079'
+export const FAUCET_URL = 'https://faucet.testnet-conway.linera.net/'
 
 export class WalletManager {
   private static _instance: WalletManager | null = null
   private wallet: Wallet | null = null
-  private signer: PrivateKeySigner | null = null
+  private signer: InMemorySigner | null = null
+  // The owner derived at index 0, which chains are claimed for
+  private owner: string | null = null
   private wasmInstance: typeof wasmType | null = null
   private isInitialized: boolean = false
 
@@ -35,8 +39,22 @@ export class WalletManager {
     if (!this.wasmInstance && !_wallet) {
       throw new Error('Missing wasmInstance or wallet')
     }
-    // await this.wasmInstance!.Wallet.setJsWallet(_wallet) // let the load() handle setting this.wallet.
-    return 'Wallet set successfully'
+    // A wallet from readJsWallet has no genesis config, which the import then
+    // takes from the stored wallet: on a fresh browser, store an empty wallet for
+    // the faucet's network first. Backups always carry their genesis config.
+    const parsed = JSON.parse(_wallet)
+    if (parsed.genesis === undefined && parsed.format === undefined) {
+      const stored = await this.wasmInstance!.Wallet.get()
+      if (stored) {
+        stored.free()
+      } else {
+        const empty = await new this.wasmInstance!.Faucet(FAUCET_URL).createWallet()
+        empty.free()
+      }
+    }
+    const report = await this.wasmInstance!.Wallet.import(_wallet, 'replace')
+    await this.load()
+    return `Wallet set successfully: ${report.imported.length} chain(s) imported`
   }
 
   /** Initialize the wallet and signer (only once) */
@@ -46,13 +64,12 @@ export class WalletManager {
     }
 
     try {
-      const secret = new this.wasmInstance!.Secret()
-      const mnemonic = PrivateKeySigner.mnemonic()
-      const signer = PrivateKeySigner.fromMnemonic(mnemonic)
-      secret.set('mn', mnemonic)
+      const signer = new this.wasmInstance!.InMemorySigner()
+      await signer.setMnemonic(this.wasmInstance!.generateMnemonic())
 
       this.wallet = wallet
       this.signer = signer
+      this.owner = await signer.deriveOwner('secp256k1', 0)
       this.isInitialized = true
     } catch (err) {
       this.cleanup()
@@ -76,24 +93,45 @@ export class WalletManager {
 
     try {
       const wallet = await this.wasmInstance!.Wallet.get()
-      const mn = await new this.wasmInstance!.Secret().get('mn')
-      const signer = PrivateKeySigner.fromMnemonic(mn)
+      const signer = new this.wasmInstance!.InMemorySigner()
+      await this.migrateLegacyMnemonic(signer)
 
       this.wallet = wallet!
-      this.signer = signer!
+      this.signer = signer
+      this.owner = await signer.deriveOwner('secp256k1', 0)
       this.isInitialized = true
     } catch (error) {
       throw new Error('Failed to read wallet')
     }
   }
 
+  /**
+   * Moves the mnemonic that older versions kept in the 'mn' secret into the
+   * signer, which derives the same owner from it at index 0.
+   */
+  private async migrateLegacyMnemonic(signer: InMemorySigner): Promise<void> {
+    const secret = new this.wasmInstance!.Secret()
+    const legacy = await secret.get('mn')
+    if (typeof legacy !== 'string') {
+      return
+    }
+    if (!(await signer.hasMnemonic())) {
+      await signer.setMnemonic(legacy)
+    }
+    await secret.set('mn', null)
+  }
+
   getWallet(): Wallet {
     return this.wallet!
   }
 
-  getSigner(): PrivateKeySigner {
+  getSigner(): InMemorySigner {
     return this.signer!
   }
+
+  getOwner(): string {
+    return this.owner!
+  }
   // is never called
   cleanup() {
     try {